@@ -0,0 +1,3 @@
+// no build steps: having a build script gives the crate an `OUT_DIR`,
+// where `#[decorate_vars]` writes the observation points manifest
+fn main() {}
@@ -1,14 +1,16 @@
-use context::HistoryContext;
+use rx_observer::history::{BufferedHistoryContext, HistoryContext, OperationType, OverflowPolicy, Query};
+use rx_observer::dataflow::DataflowContext;
 use rx_observer::prelude::*;
 
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::str::FromStr;
-use std::sync::LazyLock;
 
-mod context;
-
-static HISTORYCONTEXT: LazyLock<HistoryContext> = LazyLock::new(HistoryContext::new);
+static HISTORYCONTEXT: HistoryContext = HistoryContext::new();
+static AUDITCONTEXT: HistoryContext = HistoryContext::new();
+static DATAFLOW: DataflowContext = DataflowContext::new();
+static RECENT: HistoryContext = HistoryContext::bounded(4, OverflowPolicy::DropOldest);
+static BUFFERED: BufferedHistoryContext = BufferedHistoryContext::new();
 
 fn simple_fun(param: &i32) -> &i32 {
     param
@@ -76,14 +78,309 @@ pub fn shared_history_context_example() {
     _index2 = k + l + q;
 }
 
+//sensitive `balance` goes to the audit context only, `fee` to both
+#[decorate_vars(
+    context = HISTORYCONTEXT,
+    propose = [balance => AUDITCONTEXT, fee => [HISTORYCONTEXT, AUDITCONTEXT], net],
+    register = [],
+    request = []
+)]
+pub fn routed_history_context_example() -> i32 {
+    let balance = 100;
+    let fee = 3;
+    let net = balance - fee;
+    net
+}
+
+//observations go to whatever context the caller installed on its thread
+#[decorate_vars(
+    context = current,
+    propose = [total],
+    register = [step],
+    request = []
+)]
+pub fn ambient_history_context_example(step: i32) -> i32 {
+    let total = step * 10;
+    total
+}
+
+pub fn scoped_contexts_display() {
+    let handles = (1..=2)
+        .map(|step| {
+            std::thread::spawn(move || {
+                let local_context = HistoryContext::new();
+                rx_observer::scope::with_context(&local_context, || ambient_history_context_example(step));
+                local_context.report_data()
+            })
+        })
+        .collect::<Vec<_>>();
+    for (n, handle) in handles.into_iter().enumerate() {
+        println!("thread {n}:");
+        handle.join().unwrap().iter().for_each(|c| println!("{c}"));
+    }
+}
+
+const DEBUG_ACCOUNT_ID: i64 = 7;
+
+//observed only for the account being debugged, and every 1000th iteration
+#[decorate_vars(
+    context = HISTORYCONTEXT,
+    propose = [balance when account_id == DEBUG_ACCOUNT_ID, running when i % 1000 == 0],
+    when = account_id > 0
+)]
+pub fn conditional_history_context_example(account_id: i64) -> i64 {
+    let balance = 100 * account_id;
+    let mut total = balance;
+    for i in 0..3000 {
+        let running = total + i;
+        total = running;
+    }
+    total
+}
+
+//the invariant is reported to the context instead of panicking
+#[decorate_vars(
+    context = HISTORYCONTEXT,
+    propose = [balance],
+    check = [balance >= 0]
+)]
+pub fn checked_history_context_example(withdrawals: &[i64]) -> i64 {
+    let mut balance = 100;
+    let mut withdrawn = 0;
+    for withdrawal in withdrawals {
+        withdrawn += withdrawal;
+        balance = 100 - withdrawn;
+    }
+    balance
+}
+
+//an execution transcript of a business rule: every statement followed by the changes it made
+#[decorate_vars(
+    context = HISTORYCONTEXT,
+    propose = [fee],
+    step_trace = true
+)]
+pub fn stepped_history_context_example(amount: i64, is_member: bool) -> i64 {
+    let mut fee = amount / 100;
+    if is_member {
+        fee = amount / 200;
+    }
+    if fee < 5 {
+        fee = 5;
+    }
+    fee
+}
+
+//why a path was chosen: the branch of every `if` and the arm of every `match`
+#[decorate_vars(
+    context = HISTORYCONTEXT,
+    propose = [tier, rate],
+    decisions = true
+)]
+pub fn decided_history_context_example(score: u32) -> f64 {
+    let tier = score / 25;
+    let mut rate = match tier {
+        0 => 9.5,
+        1 | 2 => 7.0,
+        _ => 5.5,
+    };
+    if rate > 7.0 && score > 20 {
+        rate = 8.0;
+    }
+    rate
+}
+
+//early error returns leave a trace
+#[decorate_vars(
+    context = HISTORYCONTEXT,
+    propose = [quantity],
+    errors = true
+)]
+pub fn failing_history_context_example(input: &str, stock: u32) -> Result<u32, String> {
+    let quantity = input.trim().parse::<u32>().map_err(|e| e.to_string())?;
+    if quantity > stock {
+        return Err(format!("only {stock} in stock"));
+    }
+    Ok(quantity)
+}
+
+//bindings report their last observed value when they go out of scope
+#[decorate_vars(
+    context = HISTORYCONTEXT,
+    propose = [subtotal, shipping],
+    scope_end = true
+)]
+pub fn scoped_bindings_history_context_example(prices: &[u32]) -> u32 {
+    let mut subtotal = 0;
+    for price in prices {
+        let shipping = if *price < 50 { 5 } else { 0 };
+        subtotal = subtotal + price + shipping;
+    }
+    subtotal
+}
+
+//a long-running loop keeps only its latest records
+#[decorate_vars(
+    context = RECENT,
+    propose = [balance]
+)]
+pub fn bounded_history_context_example(deposits: &[i32], fee: i32) -> i32 {
+    let mut balance = 0;
+    for deposit in deposits {
+        balance = balance + deposit - fee;
+    }
+    balance
+}
+
+pub fn bounded_display() {
+    RECENT.report_data().iter().for_each(|c| println!("{}", c));
+    println!(
+        "{} records kept of {} recorded, {} dropped",
+        RECENT.log_length(),
+        RECENT.recorded(),
+        RECENT.dropped()
+    );
+}
+
+//a hot loop run by several threads appends to per-thread buffers
+#[decorate_vars(
+    context = BUFFERED,
+    propose = [checksum]
+)]
+pub fn buffered_history_context_example(worker: u32, items: u32) -> u32 {
+    let mut checksum = worker;
+    for item in 0..items {
+        checksum = (checksum * 31 + item) % 1_000;
+    }
+    checksum
+}
+
+pub fn buffered_display() {
+    std::thread::scope(|scope| {
+        for worker in 1..=3 {
+            scope.spawn(move || buffered_history_context_example(worker, 2));
+        }
+    });
+    // merged in the order of the observations
+    BUFFERED.report_data().iter().for_each(|c| println!("{}", c));
+}
+
+//which inputs influenced a value: every proposal carries its sources
+#[decorate_vars(
+    context = [HISTORYCONTEXT, DATAFLOW],
+    propose = [net, tax, total],
+    register = [price, quantity, rate],
+    provenance = true
+)]
+pub fn provenance_history_context_example(price: u32, quantity: u32, rate: u32, cap: u32) -> u32 {
+    let net = price * quantity;
+    let tax = net * rate / 100;
+    let mut total = net + tax;
+    if total > cap {
+        total = cap;
+    }
+    total
+}
+
+pub fn provenance_display() {
+    println!(
+        "inputs of total: {:?}",
+        HISTORYCONTEXT.inputs_of("provenance_history_context_example", "total")
+    );
+}
+
+pub fn query_display() {
+    let fn_name = "provenance_history_context_example";
+    println!("last total: {:?}", HISTORYCONTEXT.last_value(fn_name, "total"));
+    let large = Query::new()
+        .fn_name(fn_name)
+        .operation(OperationType::Propose)
+        .value(|value| value.parse::<i32>().is_ok_and(|value| value > 100));
+    HISTORYCONTEXT
+        .view()
+        .select(&large)
+        .for_each(|change| println!("proposed over 100: {}={}", change.ident_name, change.ident_value));
+    let mut registered = HISTORYCONTEXT.cursor(Query::new().operation(OperationType::Register));
+    let first = registered.next().map(|change| change.ident_name);
+    println!("first registered: {first:?}, then {} more", registered.count());
+}
+
+//the calls of a function told apart by their frames, for the wide export
+#[decorate_vars(
+    context = HISTORYCONTEXT,
+    propose = [subtotal, total],
+    register = [price, quantity],
+    frame = true
+)]
+pub fn exported_history_context_example(price: u32, quantity: u32) -> u32 {
+    let subtotal = price * quantity;
+    let total = subtotal + subtotal / 10;
+    total
+}
+
+pub fn export_display() {
+    let stdout = std::io::stdout();
+    println!("long format:");
+    HISTORYCONTEXT.write_csv_long(stdout.lock()).unwrap();
+    println!("wide format:");
+    HISTORYCONTEXT.write_csv_wide(stdout.lock()).unwrap();
+}
+
+pub fn dataflow_display() {
+    println!("{}", DATAFLOW.to_dot());
+    println!("{}", DATAFLOW.to_mermaid());
+}
+
+//a failing assertion shows every sub-expression
+#[decorate_vars(
+    context = HISTORYCONTEXT,
+    propose = [total],
+    power_assert = true
+)]
+pub fn power_assert_example(prices: &[i32], expected: i32) -> i32 {
+    let total = prices.iter().sum::<i32>();
+    assert_eq!(total, expected, "quote total of {} items", prices.len());
+    total
+}
+
+#[decorate_vars(
+    context = HISTORYCONTEXT,
+    propose = [total],
+    trace_all = true
+)]
+pub fn traced_history_context_example(quantity: i32, unit_price: i32) -> i32 {
+    let k = quantity * unit_price;
+    let l = k / 10;
+    let q = l.min(5);
+    let total = k + l - q;
+    println!("quote total: {total}");
+    total
+}
+
+pub fn report_audit_display() {
+    AUDITCONTEXT.report_data().iter().for_each(|c|println!("{}", c));
+}
+
 pub fn report_display() {
     HISTORYCONTEXT.report_data().iter().for_each(|c|println!("{}", c));
 }
 
+pub fn clear() {
+    HISTORYCONTEXT.clear();
+}
+
 pub fn report_json_display() {
     HISTORYCONTEXT.report_as_json().iter().for_each(|c|println!("{}", c));
 }
 
-pub fn report_data() -> Vec<String> {
-    HISTORYCONTEXT.report_data()
+pub fn callsite_filter_example() {
+    HISTORYCONTEXT.clear();
+    // every observation point of `k` costs a single atomic load now
+    rx_observer::callsite::set_filter(|callsite| callsite.ident_name() != "k");
+    shared_history_context_example();
+    rx_observer::callsite::clear_filter();
+    println!("{} records without `k`:", HISTORYCONTEXT.log_length());
+    report_display();
+    println!("callsites hit so far:");
+    rx_observer::callsite::for_each(|callsite| println!("{callsite}"));
 }
@@ -1,18 +1,35 @@
-extern crate xlformula_engine;
 
 mod snapshot;
 mod history;
+mod recorder;
+mod writer;
 mod xlformulas;
 
 fn main() {
+    println!("\n===OBSERVATION POINTS===");
+    println!("Known before any decorated function runs\n");
+    rx_observer::registry::observation_points()
+        .iter()
+        .for_each(|callsite| println!("{callsite}"));
+    println!(
+        "formula sheet names not observed anywhere: {:?}",
+        rx_observer::registry::unknown_idents(["a", "b", "c", "s"])
+    );
+    println!(
+        "build-time manifest: {}/rx_observer_examples.rx_observer.json",
+        env!("OUT_DIR")
+    );
     println!("\n===SNAPSHOT CONTEXT===");
     println!("Collects immediate values of specified variables\n");
     snapshot::snapshot_context_example();
     snapshot::another_snapshot_context_example();
     snapshot::report_display();
+    println!("as a csv table:");
+    snapshot::csv_display();
     println!("clearing context...");
     snapshot::clear_context();
     snapshot::report_display();
+    snapshot::injected_context_display();
     println!("\n===HISTORY CONTEXT===");
     println!("Collects history of changes to values of specified variables\n");
     history::history_context_example();
@@ -22,7 +39,96 @@ fn main() {
     history::report_display();
     println!("json report:");
     history::report_json_display();
+    println!("routed to history and audit contexts:");
+    history::clear();
+    history::routed_history_context_example();
+    history::report_display();
+    println!("audit context:");
+    history::report_audit_display();
+    println!("conditional observations:");
+    history::clear();
+    history::conditional_history_context_example(3);
+    history::conditional_history_context_example(7);
+    history::report_display();
+    println!("invariant checks:");
+    history::clear();
+    history::checked_history_context_example(&[60, 70]);
+    history::report_display();
+    println!("power assert (the panic message goes to stderr):");
+    let caught = std::panic::catch_unwind(|| history::power_assert_example(&[10, 20, 30], 50));
+    println!("caught the assertion failure: {}", caught.is_err());
+    println!("every intermediate value traced:");
+    history::clear();
+    history::traced_history_context_example(4, 30);
+    history::report_display();
+    println!("execution transcript:");
+    history::clear();
+    history::stepped_history_context_example(800, true);
+    history::report_display();
+    println!("decisions taken:");
+    history::clear();
+    history::decided_history_context_example(22);
+    history::decided_history_context_example(60);
+    history::report_display();
+    println!("error paths:");
+    history::clear();
+    let _ = history::failing_history_context_example("many", 5);
+    let _ = history::failing_history_context_example("12", 5);
+    let _ = history::failing_history_context_example("3", 5);
+    history::report_display();
+    println!("bindings going out of scope:");
+    history::clear();
+    history::scoped_bindings_history_context_example(&[40, 90]);
+    history::report_display();
+    println!("bounded history of the latest records:");
+    history::bounded_history_context_example(&[10, 20, 30, 40, 50, 60], 1);
+    history::bounded_display();
+    println!("buffered history merged from threads:");
+    history::buffered_display();
+    println!("provenance of proposed values:");
+    history::clear();
+    history::provenance_history_context_example(120, 3, 20, 1000);
+    history::report_display();
+    history::provenance_display();
+    println!("queried:");
+    history::query_display();
+    println!("exported for spreadsheets:");
+    history::clear();
+    history::exported_history_context_example(40, 3);
+    history::exported_history_context_example(25, 4);
+    history::export_display();
+    println!("dataflow graph:");
+    history::dataflow_display();
+    println!("scoped contexts isolated per thread:");
+    history::scoped_contexts_display();
     println!("\n===FORMULAS CONTEXT===");
     println!("When a specified variable is used in the code, it is calculated dynamically with excel-like formula\n");
     xlformulas::xlformulas_context_example();
+    println!("formulas context:");
+    xlformulas::report_display();
+    xlformulas::clear_context();
+    println!("\n===CALLSITE FILTER===");
+    println!("Disabled observation points skip the context entirely\n");
+    history::callsite_filter_example();
+    println!("\n===FLIGHT RECORDER===");
+    println!("A panic inside a decorated function dumps the call stack and the last events (to stderr)\n");
+    recorder::install_panic_hook();
+    let caught = std::panic::catch_unwind(|| recorder::load_orders_example(&[10, 20, 30]));
+    println!("caught the panic: {}", caught.is_err());
+    println!("recorded events:");
+    recorder::report_display();
+    println!("\n===BACKGROUND WRITER===");
+    println!("Events go over a channel to a thread writing them to a file\n");
+    std::thread::scope(|scope| {
+        for quantity in 1..=3 {
+            std::thread::Builder::new()
+                .name(format!("worker-{quantity}"))
+                .spawn_scoped(scope, move || writer::writer_context_example(120, quantity))
+                .unwrap();
+        }
+    });
+    writer::report_display();
+    println!("as rotated JSON Lines:");
+    writer::jsonl_context_example(120, 12);
+    writer::jsonl_display();
 }
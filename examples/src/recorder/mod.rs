@@ -0,0 +1,33 @@
+use rx_observer::prelude::*;
+use rx_observer::recorder::{DumpTarget, FlightRecorder};
+
+pub static RECORDER: FlightRecorder = FlightRecorder::new(8);
+
+#[decorate_vars(
+    context = RECORDER,
+    propose = [count],
+    frame = true
+)]
+pub fn load_orders_example(orders: &[u32]) -> u32 {
+    let count = orders.len();
+    price_order(orders, count)
+}
+
+//panics with "index out of bounds", the dump tells which index and why
+#[decorate_vars(
+    context = RECORDER,
+    propose = [index],
+    frame = true
+)]
+fn price_order(orders: &[u32], count: usize) -> u32 {
+    let index = count + 1;
+    orders[index]
+}
+
+pub fn install_panic_hook() {
+    rx_observer::recorder::install_panic_hook(&RECORDER, DumpTarget::Stderr);
+}
+
+pub fn report_display() {
+    RECORDER.events().iter().for_each(|event| println!("{}", event));
+}
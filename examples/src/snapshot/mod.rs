@@ -3,14 +3,11 @@ use std::str::FromStr;
 
 use rx_observer::prelude::*;
 
-use context::SnapshotContext;
+use rx_observer::snapshot::{KeyFormat, SnapshotContext};
 use serde::{Deserialize, Serialize};
-use std::sync::LazyLock;
 use chrono::Local;
 
-static SNAPSHOTCONTEXT: LazyLock<SnapshotContext> = LazyLock::new(SnapshotContext::new);
-
-mod context;
+static SNAPSHOTCONTEXT: SnapshotContext = SnapshotContext::new();
 
 fn simple_fun(param: &i32) -> &i32 {
     param
@@ -66,7 +63,7 @@ pub fn snapshot_context_example() {
 
 //another function using the same context
 #[decorate_vars(
-    context = SNAPSHOTCONTEXT,
+    context = crate::snapshot::SNAPSHOTCONTEXT,
     propose = [_index9, _index29, k9, my_struct9],
     register = [k9, l9, ss9],
     request = [q9, my_struct9]
@@ -94,15 +91,39 @@ pub fn another_snapshot_context_example() {
     let _struct_request9 = my_struct9;
 }
 
+//the context is injected by the caller, `ctx: &impl ObserverContext` is added to the signature
+#[decorate_vars(
+    context = param ctx,
+    propose = [total],
+    register = [price, qty],
+    request = []
+)]
+pub fn injected_snapshot_context_example(price: i32, qty: i32) -> i32 {
+    let total = price * qty;
+    total
+}
+
+pub fn injected_context_display() {
+    let local_context = SnapshotContext::with_key_format(KeyFormat::FnIdent);
+    injected_snapshot_context_example(&local_context, 3, 4);
+    println!("injected context: {:#?}", local_context.report_data());
+    let total = local_context.get::<i32>("injected_snapshot_context_example", "total");
+    println!("typed total: {total:?}");
+}
+
 pub fn report_display() {
     let now = Local::now();
     println!("{now}| snapshot context: {:#?}", SNAPSHOTCONTEXT.report_data());
+    SNAPSHOTCONTEXT
+        .iter_fn("snapshot_context_example")
+        .filter(|(ident_name, _)| ident_name.starts_with("_index"))
+        .for_each(|(ident_name, entry)| println!("{ident_name}: {} ({})", entry.value, entry.type_name));
 }
 
-pub fn report_data() -> Vec<String> {
-    SNAPSHOTCONTEXT.report_data()
+pub fn csv_display() {
+    SNAPSHOTCONTEXT.write_csv_wide(std::io::stdout().lock()).unwrap();
 }
 
 pub fn clear_context() {
-    SNAPSHOTCONTEXT.clear_context();
+    SNAPSHOTCONTEXT.clear();
 }
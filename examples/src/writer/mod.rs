@@ -0,0 +1,83 @@
+use rx_observer::prelude::*;
+use rx_observer::writer::jsonl::{JsonLinesSink, Rotation};
+use rx_observer::writer::{Backpressure, WriterConfig, WriterContext};
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+fn events_path() -> PathBuf {
+    std::env::temp_dir().join("rx_observer_examples.events.log")
+}
+
+//the observing threads only send the events, a background thread writes the file
+static WRITER: LazyLock<WriterContext> = LazyLock::new(|| {
+    let _ = std::fs::remove_file(events_path());
+    let config = WriterConfig {
+        capacity: 256,
+        flush_interval: Duration::from_millis(50),
+        backpressure: Backpressure::Block,
+    };
+    WriterContext::to_file(events_path(), config).expect("the events file can be created")
+});
+
+fn jsonl_dir() -> PathBuf {
+    std::env::temp_dir().join("rx_observer_examples.jsonl")
+}
+
+//a soak test keeps its events on disk as rotated, gzipped JSON Lines
+static JSONL: LazyLock<WriterContext> = LazyLock::new(|| {
+    let _ = std::fs::remove_dir_all(jsonl_dir());
+    std::fs::create_dir_all(jsonl_dir()).expect("the events directory can be created");
+    let rotation = Rotation {
+        max_bytes: Some(512),
+        max_age: Some(Duration::from_secs(3600)),
+        gzip: true,
+    };
+    let sink = JsonLinesSink::new(jsonl_dir().join("events.jsonl"), rotation).expect("the events file can be created");
+    WriterContext::new(sink, WriterConfig::default()).expect("the writer thread can be started")
+});
+
+#[decorate_vars(
+    context = JSONL,
+    propose = [total],
+    register = [price]
+)]
+pub fn jsonl_context_example(price: u32, rounds: u32) -> u32 {
+    let mut total = 0;
+    for round in 1..=rounds {
+        total = price * round + total / 2;
+    }
+    total
+}
+
+pub fn jsonl_display() {
+    JSONL.shutdown();
+    let mut files = std::fs::read_dir(jsonl_dir())
+        .map(|entries| entries.flatten().map(|entry| entry.file_name()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    files.sort();
+    println!("{} events in {}: {files:?}", JSONL.written(), jsonl_dir().display());
+    std::fs::read_to_string(jsonl_dir().join("events.jsonl"))
+        .unwrap_or_default()
+        .lines()
+        .for_each(|line| println!("{line}"));
+}
+
+#[decorate_vars(
+    context = WRITER,
+    propose = [subtotal],
+    register = [price, quantity]
+)]
+pub fn writer_context_example(price: u32, quantity: u32) -> u32 {
+    let subtotal = price * quantity;
+    subtotal
+}
+
+pub fn report_display() {
+    WRITER.shutdown();
+    println!("{} events written to {}:", WRITER.written(), events_path().display());
+    std::fs::read_to_string(events_path())
+        .unwrap_or_default()
+        .lines()
+        .for_each(|line| println!("{line}"));
+}
@@ -1,11 +1,10 @@
 use rx_observer::prelude::decorate_vars;
 use rx_observer::ObserverContext;
+use rx_observer::formulas::FormulasContext;
 use std::sync::LazyLock;
 
-mod context;
-
-static FORMULASCONTEXT: LazyLock<context::FormulasContext> = LazyLock::new(|| {
-    context::FormulasContext::new(vec![("a", "=(b+c)*s"), ("s", "=SUM({b, c, 1})")])
+static FORMULASCONTEXT: LazyLock<FormulasContext> = LazyLock::new(|| {
+    FormulasContext::new(vec![("a", "=(b+c)*s"), ("s", "=SUM({b, c, 1})")])
 });
 
 #[decorate_vars(
@@ -15,12 +14,20 @@ static FORMULASCONTEXT: LazyLock<context::FormulasContext> = LazyLock::new(|| {
     request = [a]
 )]
 pub fn xlformulas_context_example() {
-    let b = 4; // registering `b`
-    let c = 11; // registering `c`
+    let b: i32 = 4; // registering `b`
+    let c: i32 = 11; // registering `c`
     let a = 0;
     let w = a; // requested `a` is calculated in formula 
-    println!("b = 4; c = 11;");
+    println!("b = {b}; c = {c};");
     println!("formula for a: =(b+c)*s");
     println!("formula for s: =SUM({{b, c, 1}})");
     println!("a = {w}");
 }
+
+pub fn report_display() {
+    FORMULASCONTEXT.report_data().iter().for_each(|c| println!("{}", c));
+}
+
+pub fn clear_context() {
+    FORMULASCONTEXT.clear();
+}
@@ -0,0 +1,224 @@
+//! Capturing the values of sub-expressions while they are evaluated,
+//! for `power_assert` and `trace_all`.
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::fold::{self, Fold};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    BinOp, Expr, ExprArray, ExprBinary, ExprCall, ExprCast, ExprRange, ExprReference, ExprRepeat, ExprTuple, ExprUnary, Token,
+    UnOp,
+};
+
+/// emits the code handling a captured value: gets the source text and the span of the sub-expression
+/// and an expression of a reference to its value
+pub(crate) type CaptureSink<'s> = &'s dyn Fn(&str, Span, TokenStream2) -> TokenStream2;
+
+/// instruments sub-expressions so that their values go to the sink while they are evaluated;
+/// places (variables, fields, indexing, dereferences) are never moved:
+/// comparisons borrow their operands, other positions keep their original by-value semantics.
+/// A variable is captured through a reference right before the innermost instrumented expression reading it,
+/// and read in place, so a `&mut` is still reborrowed and a non-`Copy` local may be read twice
+pub(crate) struct CapturingFolder<'s> {
+    pub(crate) capture: CaptureSink<'s>,
+    /// the captures of the variables read by the expressions being folded, not emitted yet
+    pending: Vec<TokenStream2>,
+}
+
+pub(crate) fn text(expr: &impl quote::ToTokens) -> String {
+    quote!(#expr).to_string()
+}
+
+pub(crate) fn is_place(expr: &Expr) -> bool {
+    match expr {
+        Expr::Field(_) | Expr::Index(_) => true,
+        Expr::Unary(ExprUnary { op: UnOp::Deref(_), .. }) => true,
+        Expr::Paren(paren) => is_place(&paren.expr),
+        Expr::Path(_) => true,
+        _ => false,
+    }
+}
+
+/// plain variables by convention; constants, unit structs and variants like `None` are left alone
+fn is_variable(expr: &Expr) -> bool {
+    matches!(expr, Expr::Path(path) if path.path.get_ident().is_some_and(|ident| {
+        ident.to_string().starts_with(|c: char| c.is_lowercase() || c == '_')
+    }))
+}
+
+fn is_comparison(op: &BinOp) -> bool {
+    matches!(
+        op,
+        BinOp::Eq(_) | BinOp::Ne(_) | BinOp::Lt(_) | BinOp::Le(_) | BinOp::Gt(_) | BinOp::Ge(_)
+    )
+}
+
+/// `a += b`, ..., whose left side is a place and whose value is `()`
+fn is_compound_assignment(op: &BinOp) -> bool {
+    matches!(
+        op,
+        BinOp::AddAssign(_)
+            | BinOp::SubAssign(_)
+            | BinOp::MulAssign(_)
+            | BinOp::DivAssign(_)
+            | BinOp::RemAssign(_)
+            | BinOp::BitXorAssign(_)
+            | BinOp::BitAndAssign(_)
+            | BinOp::BitOrAssign(_)
+            | BinOp::ShlAssign(_)
+            | BinOp::ShrAssign(_)
+    )
+}
+
+impl<'s> CapturingFolder<'s> {
+    pub(crate) fn new(capture: CaptureSink<'s>) -> Self {
+        CapturingFolder {
+            capture,
+            pending: Vec::new(),
+        }
+    }
+
+    /// `expr` folded, with the captures of the variables it reads directly placed before it
+    pub(crate) fn fold_root(&mut self, expr: Expr) -> Expr {
+        let expr = self.fold_expr(expr);
+        let variables = std::mem::take(&mut self.pending);
+        if variables.is_empty() {
+            expr
+        } else {
+            syn::parse_quote!({ #(#variables)* #expr })
+        }
+    }
+
+    /// `{ <variable captures>; let __rx_value = expr; <capture>; __rx_value }`, with the captures of the variables
+    /// read since `pending` had the length `variables`
+    fn captured_value(&mut self, variables: usize, text: &str, span: Span, expr: TokenStream2) -> Expr {
+        let variables = self.pending.split_off(variables);
+        let capture = (self.capture)(text, span, quote!(&__rx_value));
+        syn::parse_quote! {
+            {
+                #(#variables)*
+                let __rx_value = #expr;
+                #capture
+                __rx_value
+            }
+        }
+    }
+
+    /// a comparison operand, borrowed in the comparison; its value is captured through the reference
+    pub(crate) fn fold_operand(&mut self, operand: Expr, reference: TokenStream2) -> (Expr, TokenStream2) {
+        if is_place(&operand) {
+            let capture = (self.capture)(&text(&operand), operand.span(), reference);
+            (operand, capture)
+        } else {
+            (self.fold_root(operand), quote!())
+        }
+    }
+}
+
+impl Fold for CapturingFolder<'_> {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        let expr_text = text(&expr);
+        let span = expr.span();
+        let variables = self.pending.len();
+        match expr {
+            Expr::Binary(ExprBinary { left, op, right, .. }) if is_comparison(&op) => {
+                let (left, left_capture) = self.fold_operand(*left, quote!(__rx_left));
+                let (right, right_capture) = self.fold_operand(*right, quote!(__rx_right));
+                let result_capture = (self.capture)(&expr_text, span, quote!(&__rx_value));
+                // the operands read a variable directly only as a place, captured through their reference
+                let variables = self.pending.split_off(variables);
+                syn::parse_quote! {
+                    match (&(#left), &(#right)) {
+                        (__rx_left, __rx_right) => {
+                            #(#variables)*
+                            #left_capture
+                            #right_capture
+                            let __rx_value = *__rx_left #op *__rx_right;
+                            #result_capture
+                            __rx_value
+                        }
+                    }
+                }
+            }
+            Expr::Binary(ExprBinary { left, op, right, .. }) if is_compound_assignment(&op) => {
+                let right = self.fold_expr(*right);
+                let variables = self.pending.split_off(variables);
+                syn::parse_quote!({ #(#variables)* #left #op #right })
+            }
+            Expr::Binary(ExprBinary { left, op, right, .. }) => {
+                let left = self.fold_expr(*left);
+                let right = self.fold_expr(*right);
+                self.captured_value(variables, &expr_text, span, quote!(#left #op #right))
+            }
+            Expr::Path(_) if is_variable(&expr) => {
+                let capture = (self.capture)(&expr_text, span, quote!(&#expr));
+                self.pending.push(capture);
+                expr
+            }
+            Expr::Unary(ExprUnary { op: UnOp::Deref(_), .. }) => expr,
+            Expr::Unary(ExprUnary { op, expr: operand, .. }) => {
+                let operand = self.fold_expr(*operand);
+                self.captured_value(variables, &expr_text, span, quote!(#op #operand))
+            }
+            Expr::Reference(ExprReference { mutability, expr: referent, .. }) if is_place(&referent) => {
+                self.captured_value(variables, &expr_text, span, quote!(& #mutability #referent))
+            }
+            Expr::MethodCall(call) => {
+                // a place receiver may be auto-referenced, so it is left as is
+                let receiver = if is_place(&call.receiver) {
+                    *call.receiver
+                } else {
+                    self.fold_expr(*call.receiver)
+                };
+                let args = call
+                    .args
+                    .into_iter()
+                    .map(|arg| self.fold_expr(arg))
+                    .collect::<Punctuated<Expr, Token![,]>>();
+                let method = call.method;
+                let turbofish = call.turbofish;
+                self.captured_value(variables, &expr_text, span, quote!(#receiver.#method #turbofish(#args)))
+            }
+            Expr::Call(ExprCall { func, args, .. }) => {
+                let args = args
+                    .into_iter()
+                    .map(|arg| self.fold_expr(arg))
+                    .collect::<Punctuated<Expr, Token![,]>>();
+                self.captured_value(variables, &expr_text, span, quote!(#func(#args)))
+            }
+            Expr::Cast(ExprCast { expr: operand, ty, .. }) => {
+                let operand = self.fold_expr(*operand);
+                self.captured_value(variables, &expr_text, span, quote!((#operand) as #ty))
+            }
+            Expr::Range(ExprRange { start, limits, end, .. }) => {
+                let start = start.map(|start| self.fold_expr(*start));
+                let end = end.map(|end| self.fold_expr(*end));
+                self.captured_value(variables, &expr_text, span, quote!(#start #limits #end))
+            }
+            Expr::Tuple(ExprTuple { elems, .. }) if !elems.is_empty() => {
+                let elems = elems
+                    .into_iter()
+                    .map(|elem| self.fold_expr(elem))
+                    .collect::<Punctuated<Expr, Token![,]>>();
+                // a single element keeps its trailing comma
+                self.captured_value(variables, &expr_text, span, quote!((#elems,)))
+            }
+            Expr::Array(ExprArray { elems, .. }) => {
+                let elems = elems
+                    .into_iter()
+                    .map(|elem| self.fold_expr(elem))
+                    .collect::<Punctuated<Expr, Token![,]>>();
+                self.captured_value(variables, &expr_text, span, quote!([#elems]))
+            }
+            // the length is a constant
+            Expr::Repeat(ExprRepeat { expr: elem, len, .. }) => {
+                let elem = self.fold_expr(*elem);
+                self.captured_value(variables, &expr_text, span, quote!([#elem; #len]))
+            }
+            Expr::Paren(_) => fold::fold_expr(self, expr),
+            // closures, blocks, literals, macros, ... are evaluated as written
+            _ => expr,
+        }
+    }
+}
+
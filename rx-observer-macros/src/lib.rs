@@ -1,87 +1,341 @@
+mod capture;
+mod manifest;
+mod power_assert;
+
+use capture::{text, CapturingFolder};
+use manifest::{FunctionEntry, PointEntry};
 use proc_macro::TokenStream;
-use quote::quote;
+use proc_macro2::Span;
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ops::Deref;
 use syn::fold::{self, Fold};
 use syn::parse::{Parse, ParseStream};
+use syn::visit::{self, Visit};
 use syn::{
-    parse_macro_input, parse_quote, punctuated::Punctuated, Expr, ExprAssign, ExprBinary, ExprCall, ExprPath, Ident, ItemFn, Local,
-    Pat, Stmt, Token,
+    parse_macro_input, parse_quote, punctuated::Punctuated, Block, Expr, ExprAssign, ExprBinary, ExprCall, ExprForLoop, ExprIf,
+    ExprMatch, ExprPath, ExprReturn, ExprTry, ExprWhile, FnArg, Ident, ItemFn, Local, Pat, PatType, ReturnType, Stmt, Token,
+    Type,
 };
 
+/// a context observations are delegated to:
+/// any expression (`CTX`, `crate::obs::CTX`, `self.ctx`),
+/// a function parameter `param ctx` / `param ctx: Type`, added to the signature when it is missing,
+/// or `current`, the innermost context installed with `rx_observer::scope`
+#[derive(Clone)]
+enum Context {
+    Expr(Expr),
+    Param(Ident, Option<Type>),
+}
+
+impl Parse for Context {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Ident) && input.peek2(Ident) && input.fork().parse::<Ident>()? == "param" {
+            input.parse::<Ident>()?;
+            let ident: Ident = input.parse()?;
+            let ty = if input.peek(Token![:]) {
+                input.parse::<Token![:]>()?;
+                Some(input.parse()?)
+            } else {
+                None
+            };
+            return Ok(Context::Param(ident, ty));
+        }
+        let expr: Expr = input.parse()?;
+        if matches!(&expr, Expr::Path(ExprPath { path, .. }) if path.is_ident("current")) {
+            return Ok(Context::Expr(parse_quote!(::rx_observer::scope::Current)));
+        }
+        Ok(Context::Expr(expr))
+    }
+}
+
+/// the receiver of the delegate call, e.g. `#context.propose(...)`
+impl ToTokens for Context {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        match self {
+            Context::Expr(
+                expr @ (Expr::Path(_) | Expr::Field(_) | Expr::MethodCall(_) | Expr::Call(_) | Expr::Index(_) | Expr::Paren(_)),
+            ) => expr.to_tokens(tokens),
+            Context::Expr(expr) => quote!((#expr)).to_tokens(tokens),
+            Context::Param(ident, _) => ident.to_tokens(tokens),
+        }
+    }
+}
+
+/// one context `ctx` or a fan-out to several ones `[ctx1, ctx2, ...]`
+#[derive(Clone)]
+struct Contexts(Vec<Context>);
+
+impl Parse for Contexts {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let contexts: Vec<Context> = if input.peek(syn::token::Bracket) {
+            let content;
+            syn::bracketed!(content in input);
+            content.parse_terminated(Context::parse, Token![,])?.into_iter().collect()
+        } else {
+            vec![input.parse()?]
+        };
+        if contexts.is_empty() {
+            return Err(input.error("expected at least one context"));
+        }
+        Ok(Contexts(contexts))
+    }
+}
+
+/// an entry of `propose`/`register`/`request` lists:
+/// `ident` goes to the function's contexts, `ident => contexts` is routed to its own ones,
+/// `ident when condition` is observed only while the condition holds
+struct ObservedIdent {
+    ident: Ident,
+    contexts: Option<Contexts>,
+    when: Option<Expr>,
+}
+
+impl Parse for ObservedIdent {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        let contexts = if input.peek(Token![=>]) {
+            input.parse::<Token![=>]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        let when = if input.peek(Ident) && input.fork().parse::<Ident>()? == "when" {
+            input.parse::<Ident>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        Ok(ObservedIdent { ident, contexts, when })
+    }
+}
+
 struct MacroParams {
-    context: Ident,
-    propose: Punctuated<Ident, Token![,]>,
-    register: Punctuated<Ident, Token![,]>,
-    request: Punctuated<Ident, Token![,]>,
+    context: Contexts,
+    propose: Punctuated<ObservedIdent, Token![,]>,
+    register: Punctuated<ObservedIdent, Token![,]>,
+    request: Punctuated<ObservedIdent, Token![,]>,
+    when: Option<Expr>,
+    check: Punctuated<Expr, Token![,]>,
+    power_assert: bool,
+    trace_all: bool,
+    step_trace: bool,
+    decisions: bool,
+    errors: bool,
+    frame: bool,
+    scope_end: bool,
+    provenance: bool,
 }
 
 impl Parse for MacroParams {
-    /// very simple streaming parsing of #[decorate_vars()] parameters
+    /// very simple streaming parsing of #[decorate_vars()] parameters,
+    /// `context` is required, the rest are optional and can go in any order
     /// e.g.
     /// #[decorate_vars(
-    //     context = context_ident,
-    //     propose = [ident1, ident2, ...],
+    //     context = context_expr | param context_param | current | [context_expr1, context_expr2, ...],
+    //     propose = [ident1, ident2 => context_expr, ident3 => [context_expr1, ...] when condition, ...],
     //     register = [ident1, ident2, ...],
-    //     request = [ident1, ident2, ...]
+    //     request = [ident1, ident2, ...],
+    //     when = condition,
+    //     check = [invariant1, invariant2, ...],
+    //     power_assert = true | false,
+    //     trace_all = true | false,
+    //     step_trace = true | false,
+    //     decisions = true | false,
+    //     errors = true | false,
+    //     frame = true | false,
+    //     scope_end = true | false,
+    //     provenance = true | false
     // )]
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let context_keyword: Ident = input.parse()?;
-        if context_keyword != "context" {
-            return Err(input.error("expected `context` keyword"));
+        let mut context = None;
+        let mut propose = None;
+        let mut register = None;
+        let mut request = None;
+        let mut when = None;
+        let mut check = None;
+        let mut power_assert = None;
+        let mut trace_all = None;
+        let mut step_trace = None;
+        let mut decisions = None;
+        let mut errors = None;
+        let mut frame = None;
+        let mut scope_end = None;
+        let mut provenance = None;
+
+        while !input.is_empty() {
+            let keyword: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let duplicate = match keyword.to_string().as_str() {
+                "context" => context.replace(input.parse::<Contexts>()?).is_some(),
+                "propose" | "register" | "request" => {
+                    let content;
+                    syn::bracketed!(content in input);
+                    let list = content.parse_terminated(ObservedIdent::parse, Token![,])?;
+                    match keyword.to_string().as_str() {
+                        "propose" => propose.replace(list).is_some(),
+                        "register" => register.replace(list).is_some(),
+                        _ => request.replace(list).is_some(),
+                    }
+                }
+                "when" => when.replace(input.parse::<Expr>()?).is_some(),
+                "check" => {
+                    let content;
+                    syn::bracketed!(content in input);
+                    check
+                        .replace(content.parse_terminated(Expr::parse, Token![,])?)
+                        .is_some()
+                }
+                "power_assert" => power_assert.replace(input.parse::<syn::LitBool>()?.value).is_some(),
+                "trace_all" => trace_all.replace(input.parse::<syn::LitBool>()?.value).is_some(),
+                "step_trace" => step_trace.replace(input.parse::<syn::LitBool>()?.value).is_some(),
+                "decisions" => decisions.replace(input.parse::<syn::LitBool>()?.value).is_some(),
+                "errors" => errors.replace(input.parse::<syn::LitBool>()?.value).is_some(),
+                "frame" => frame.replace(input.parse::<syn::LitBool>()?.value).is_some(),
+                "scope_end" => scope_end.replace(input.parse::<syn::LitBool>()?.value).is_some(),
+                "provenance" => provenance.replace(input.parse::<syn::LitBool>()?.value).is_some(),
+                _ => {
+                    return Err(syn::Error::new(
+                        keyword.span(),
+                        "expected `context`, `propose`, `register`, `request`, `when`, `check`, `power_assert`, `trace_all`, `step_trace`, `decisions`, `errors`, `frame`, `scope_end` or `provenance` keyword",
+                    ));
+                }
+            };
+            if duplicate {
+                return Err(syn::Error::new(keyword.span(), format!("duplicate `{keyword}` keyword")));
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
         }
-        input.parse::<Token![=]>()?;
 
-        let context: Ident = input.parse()?;
-        input.parse::<Token![,]>()?;
+        let Some(context) = context else {
+            return Err(input.error("expected `context` keyword"));
+        };
+        Ok(MacroParams {
+            context,
+            propose: propose.unwrap_or_default(),
+            register: register.unwrap_or_default(),
+            request: request.unwrap_or_default(),
+            when,
+            check: check.unwrap_or_default(),
+            power_assert: power_assert.unwrap_or(false),
+            trace_all: trace_all.unwrap_or(false),
+            step_trace: step_trace.unwrap_or(false),
+            decisions: decisions.unwrap_or(false),
+            errors: errors.unwrap_or(false),
+            frame: frame.unwrap_or(false),
+            scope_end: scope_end.unwrap_or(false),
+            provenance: provenance.unwrap_or(false),
+        })
+    }
+}
+
+/// variables a `check` expression mentions, i.e. plain identifiers except called functions
+#[derive(Default)]
+struct MentionedIdents(Vec<Ident>);
 
-        let propose_keyword: Ident = input.parse()?;
-        if propose_keyword != "propose" {
-            return Err(input.error("expected `propose` keyword"));
+impl<'ast> Visit<'ast> for MentionedIdents {
+    fn visit_expr_path(&mut self, expr: &'ast ExprPath) {
+        if let Some(ident) = expr.path.get_ident()
+            && !self.0.contains(ident)
+        {
+            self.0.push(ident.clone());
         }
-        input.parse::<Token![=]>()?;
+        visit::visit_expr_path(self, expr);
+    }
 
-        let propose_content;
-        syn::bracketed!(propose_content in input);
-        let propose = propose_content.parse_terminated(Ident::parse, Token![,])?;
-        input.parse::<Token![,]>()?;
+    fn visit_expr_call(&mut self, expr: &'ast ExprCall) {
+        expr.args.iter().for_each(|arg| self.visit_expr(arg));
+    }
+}
 
-        let register_keyword: Ident = input.parse()?;
-        if register_keyword != "register" {
-            return Err(input.error("expected `register` keyword"));
-        }
-        input.parse::<Token![=]>()?;
+/// the identifiers a pattern binds
+#[derive(Default)]
+struct PatIdents(Vec<Ident>);
 
-        let register_content;
-        syn::bracketed!(register_content in input);
-        let register = register_content.parse_terminated(Ident::parse, Token![,])?;
-        input.parse::<Token![,]>()?;
+impl<'ast> Visit<'ast> for PatIdents {
+    fn visit_pat_ident(&mut self, pat: &'ast syn::PatIdent) {
+        self.0.push(pat.ident.clone());
+        visit::visit_pat_ident(self, pat);
+    }
+}
 
-        let request_keyword: Ident = input.parse()?;
-        if request_keyword != "request" {
-            return Err(input.error("expected `request` keyword"));
-        }
-        input.parse::<Token![=]>()?;
+fn pat_idents(pat: &Pat) -> Vec<Ident> {
+    let mut idents = PatIdents::default();
+    idents.visit_pat(pat);
+    idents.0
+}
 
-        let request_content;
-        syn::bracketed!(request_content in input);
-        let request = request_content.parse_terminated(Ident::parse, Token![,])?;
+/// the identifiers bound by the `let` statements of a function body, nested items aside
+#[derive(Default)]
+struct LetBindings(Vec<Ident>);
 
-        Ok(MacroParams {
-            context,
-            propose,
-            register,
-            request,
-        })
+impl<'ast> Visit<'ast> for LetBindings {
+    fn visit_local(&mut self, local: &'ast Local) {
+        self.0.extend(pat_idents(&local.pat));
+        visit::visit_local(self, local);
     }
+
+    fn visit_item(&mut self, _item: &'ast syn::Item) {}
 }
 
 #[proc_macro_attribute]
 pub fn decorate_vars(attr: TokenStream, item: TokenStream) -> TokenStream {
     let params = parse_macro_input!(attr as MacroParams);
 
-    let input_fn = parse_macro_input!(item as ItemFn);
+    let mut input_fn = parse_macro_input!(item as ItemFn);
+    // the frame guard pops the stack of the thread it was pushed on, so it can't be held across an `.await`
+    if params.frame
+        && let Some(asyncness) = &input_fn.sig.asyncness
+    {
+        return syn::Error::new(
+            asyncness.span,
+            "`frame = true` is not supported on `async fn`: the frame belongs to the calling thread's stack \
+             and would make the future `!Send`",
+        )
+        .to_compile_error()
+        .into();
+    }
+    inject_context_params(&params, &mut input_fn);
 
     let func_name = input_fn.sig.ident.to_string();
+    let fn_span = input_fn.sig.ident.span();
+
+    // parameter types are known upfront, `let` annotations are collected while folding
+    let types = input_fn
+        .sig
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(PatType { pat, ty, .. }) => match pat.deref() {
+                Pat::Ident(p) => Some((p.ident.to_string(), quote!(#ty).to_string())),
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect();
+
+    // the parameters are bound from the start, the `let` bindings as they are declared
+    let parameters = input_fn
+        .sig
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(PatType { pat, .. }) => Some(pat_idents(pat)),
+            FnArg::Receiver(_) => None,
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    let mut locals = LetBindings(parameters.clone());
+    locals.visit_block(&input_fn.block);
+
+    // `return Err(..)` is observed through a binding of the return type, which `impl Trait` can't name
+    let return_type = match &input_fn.sig.output {
+        ReturnType::Type(_, ty) if !matches!(ty.deref(), Type::ImplTrait(_)) => Some(ty.deref().clone()),
+        _ => None,
+    };
 
     let mut folder = DecoratingFolder {
         context: params.context,
@@ -89,10 +343,81 @@ pub fn decorate_vars(attr: TokenStream, item: TokenStream) -> TokenStream {
         propose: params.propose.into_iter().collect(),
         register: params.register.into_iter().collect(),
         request: params.request.into_iter().collect(),
+        when: params.when,
+        checks: params.check.into_iter().collect(),
+        locals: locals.0,
+        bound: parameters,
+        evaluated_checks: Vec::new(),
+        unbound_checks: Vec::new(),
+        power_assert: params.power_assert,
+        trace_all: params.trace_all,
+        step_trace: params.step_trace,
+        decisions: params.decisions,
+        errors: params.errors,
+        return_type,
+        nested: false,
+        scope_end: params.scope_end,
+        scoped: Vec::new(),
+        provenance: params.provenance,
+        types,
+        points: Vec::new(),
     };
 
     // A freestanding function: `fn process(n: usize) -> Result<()> { ... }`.
-    let transformed_fn = folder.fold_item_fn(input_fn);
+    let mut transformed_fn = folder.fold_item_fn(input_fn);
+
+    // a check never evaluated would silently check nothing
+    let mut unevaluated = folder
+        .unbound_checks
+        .iter()
+        .enumerate()
+        .filter(|(position, (index, _))| {
+            !folder.evaluated_checks.contains(index)
+                && !folder.unbound_checks[..*position].iter().any(|(earlier, _)| earlier == index)
+        })
+        .map(|(_, unbound)| unbound)
+        .map(|(index, unbound)| {
+            let check = &folder.checks[*index];
+            syn::Error::new_spanned(
+                check,
+                format!(
+                    "`{}` mentions `{unbound}`, which is not bound yet at any proposal of the variables it mentions; \
+                     propose `{unbound}` too, or declare it earlier",
+                    text(check)
+                ),
+            )
+        });
+    if let Some(mut error) = unevaluated.next() {
+        unevaluated.for_each(|other| error.combine(other));
+        return error.to_compile_error().into();
+    }
+
+    // the sequence numbers of the latest proposals of the call
+    if params.provenance {
+        for observed in folder.propose.iter().rev() {
+            let sequence = format_ident!("__rx_seq_{}", observed.ident);
+            transformed_fn.block.stmts.insert(
+                0,
+                parse_quote!(#[allow(unused_mut)] let mut #sequence: ::std::option::Option<u64> = ::std::option::Option::None;),
+            );
+        }
+    }
+
+    // the frame of the call on the thread's stack of decorated calls, for `rx_observer::recorder`
+    if params.frame {
+        let fn_name = &folder.fn_name;
+        transformed_fn
+            .block
+            .stmts
+            .insert(0, parse_quote!(let __rx_frame = ::rx_observer::recorder::enter(#fn_name);));
+    }
+
+    manifest::record(FunctionEntry {
+        function: folder.fn_name,
+        file: fn_span.file(),
+        line: fn_span.start().line,
+        points: folder.points,
+    });
 
     let output = quote! {
         #transformed_fn
@@ -101,23 +426,607 @@ pub fn decorate_vars(attr: TokenStream, item: TokenStream) -> TokenStream {
     output.into()
 }
 
+/// adds `param` contexts missing from the signature right after the receiver, if there is one;
+/// without a type they are `&impl ObserverContext`
+fn inject_context_params(params: &MacroParams, input_fn: &mut ItemFn) {
+    let entry_contexts = params
+        .propose
+        .iter()
+        .chain(params.register.iter())
+        .chain(params.request.iter())
+        .filter_map(|observed| observed.contexts.as_ref());
+    let mut position = input_fn
+        .sig
+        .inputs
+        .iter()
+        .take_while(|arg| matches!(arg, FnArg::Receiver(_)))
+        .count();
+    for context in std::iter::once(&params.context)
+        .chain(entry_contexts)
+        .flat_map(|contexts| contexts.0.iter())
+    {
+        let Context::Param(ident, ty) = context else {
+            continue;
+        };
+        let present = input_fn.sig.inputs.iter().any(|arg| match arg {
+            FnArg::Typed(PatType { pat, .. }) => matches!(pat.deref(), Pat::Ident(p) if &p.ident == ident),
+            FnArg::Receiver(_) => false,
+        });
+        if present {
+            continue;
+        }
+        let arg: FnArg = match ty {
+            Some(ty) => parse_quote!(#ident: #ty),
+            None => parse_quote!(#ident: &impl for<'__rx> ::rx_observer::ObserverContext<'__rx>),
+        };
+        input_fn.sig.inputs.insert(position, arg);
+        position += 1;
+    }
+}
+
 /// folding object for syn::gen::fold
 /// wraps specified identifiers into decorators
 /// for delegating to the ObserverContext
 struct DecoratingFolder {
-    context: Ident,
+    context: Contexts,
     fn_name: String,
-    propose: Vec<Ident>,
-    register: Vec<Ident>,
-    request: Vec<Ident>,
+    propose: Vec<ObservedIdent>,
+    register: Vec<ObservedIdent>,
+    request: Vec<ObservedIdent>,
+    /// the function-wide condition of every observation
+    when: Option<Expr>,
+    /// invariants evaluated after each proposal of a variable they mention
+    checks: Vec<Expr>,
+    /// the parameters and `let` bindings of the function, a check mentioning one is evaluated only where it is bound
+    locals: Vec<Ident>,
+    /// the locals bound at the folded code
+    bound: Vec<Ident>,
+    /// the indices of the checks evaluated after some proposal
+    evaluated_checks: Vec<usize>,
+    /// the indices of the checks left out after a proposal, with a local they mention not bound there
+    unbound_checks: Vec<(usize, Ident)>,
+    /// whether `assert!`, `assert_eq!` and `assert_ne!` show their sub-expression values on failure
+    power_assert: bool,
+    /// whether the intermediate values of the statements go to the `trace` delegate
+    trace_all: bool,
+    /// whether every statement is reported to the `step` delegate before it runs
+    step_trace: bool,
+    /// whether the branch taken by every `if` and `match` is reported to the `decision` delegate
+    decisions: bool,
+    /// whether errors short-circuited by `?` and returned by `return Err(..)` are reported to the `error` delegate
+    errors: bool,
+    /// the return type of the function, `None` when it can't be named
+    return_type: Option<Type>,
+    /// whether the folded code is in a closure or an async block, which `?` and `return` leave instead of the function
+    nested: bool,
+    /// whether observed `let` bindings report their last observed value to the `scope_end` delegate when dropped
+    scope_end: bool,
+    /// bindings with a scope-end guard in the enclosing blocks
+    scoped: Vec<Ident>,
+    /// whether proposals carry their sequence number and their observed sources
+    provenance: bool,
+    /// known type annotations of identifiers, for the manifest
+    types: HashMap<String, String>,
+    /// observation points emitted so far, for the manifest
+    points: Vec<PointEntry>,
+}
+
+/// the `ObserverContext` delegate an observation point is routed to
+#[derive(Copy, Clone)]
+enum ObservationKind {
+    Register,
+    Propose,
+    Request,
+}
+
+impl ObservationKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ObservationKind::Register => "register",
+            ObservationKind::Propose => "propose",
+            ObservationKind::Request => "request",
+        }
+    }
+}
+
+impl DecoratingFolder {
+    /// the list entry of `ident` for the delegate, if it is observed by it
+    fn observed(&self, kind: ObservationKind, ident: &Ident) -> Option<&ObservedIdent> {
+        let list = match kind {
+            ObservationKind::Register => &self.register,
+            ObservationKind::Propose => &self.propose,
+            ObservationKind::Request => &self.request,
+        };
+        list.iter().find(|observed| &observed.ident == ident)
+    }
+
+    fn is_observed(&self, kind: ObservationKind, ident: &Ident) -> bool {
+        self.observed(kind, ident).is_some()
+    }
+
+    fn is_observed_any(&self, ident: &Ident) -> bool {
+        [ObservationKind::Propose, ObservationKind::Register, ObservationKind::Request]
+            .into_iter()
+            .any(|kind| self.is_observed(kind, ident))
+    }
+
+    /// `(name, value)` pairs of the observed variables among the mentioned ones
+    fn observed_values(&self, mentioned: &MentionedIdents) -> Vec<proc_macro2::TokenStream> {
+        mentioned
+            .0
+            .iter()
+            .filter(|mentioned| self.is_observed_any(mentioned))
+            .map(|mentioned| {
+                let name = mentioned.to_string();
+                quote!((#name, #mentioned.to_string()))
+            })
+            .collect()
+    }
+
+    /// evaluates the `check` invariants mentioning the just proposed `ident` under the conditions of its proposal,
+    /// the ones mentioning a local not bound yet aside;
+    /// a violation is reported to the function's contexts with the values of the observed variables it mentions
+    fn checks_after(&mut self, ident: &Ident) -> proc_macro2::TokenStream {
+        let fn_name = &self.fn_name;
+        let condition = self.condition(ObservationKind::Propose, ident);
+        let start = ident.span().start();
+        let mut checks = Vec::new();
+        let mut points = Vec::new();
+        for (index, check) in self.checks.iter().enumerate() {
+            let mut mentioned = MentionedIdents::default();
+            mentioned.visit_expr(check);
+            if !mentioned.0.contains(ident) {
+                continue;
+            }
+            // e.g. a variable declared after the proposed one, the check is left to its proposal
+            let unbound = mentioned
+                .0
+                .iter()
+                .find(|mentioned| *mentioned != ident && self.locals.contains(mentioned) && !self.bound.contains(mentioned));
+            if let Some(unbound) = unbound {
+                self.unbound_checks.push((index, unbound.clone()));
+                continue;
+            }
+            self.evaluated_checks.push(index);
+            let invariant = text(check);
+            points.push(PointEntry {
+                variable: invariant.clone(),
+                kind: String::from("check"),
+                type_name: None,
+                line: start.line,
+                column: start.column + 1,
+            });
+            let values = self.observed_values(&mentioned);
+            let callsite = self.callsite(quote!(Check), &invariant, ident.span());
+            let reports = self.context.0.iter().map(|context| {
+                quote! {
+                    #context.check_failed(__RX_CALLSITE.ident_name(), #fn_name, __rx_values);
+                }
+            });
+            checks.push(quote! {
+                {
+                    #callsite
+                    if __RX_CALLSITE.is_enabled() #condition && !(#check) {
+                        let __rx_values: &[(&'static str, String)] = &[#(#values),*];
+                        #(#reports)*
+                    }
+                }
+            });
+        }
+        self.points.extend(points);
+        quote!(#(#checks)*)
+    }
+
+    /// the conditions an observation of `ident` is made under, `&& (when)` for each of the function's and its own
+    fn condition(&self, kind: ObservationKind, ident: &Ident) -> proc_macro2::TokenStream {
+        let observed = self.observed(kind, ident);
+        match (&self.when, observed.and_then(|observed| observed.when.as_ref())) {
+            (Some(fn_when), Some(ident_when)) => quote!(&& (#fn_when) && (#ident_when)),
+            (Some(when), None) | (None, Some(when)) => quote!(&& (#when)),
+            (None, None) => quote!(),
+        }
+    }
+
+    /// wraps `value` of the observed `ident` into calls of the context delegates,
+    /// guarded by a static `rx_observer::callsite::Callsite` describing this observation point;
+    /// with several contexts each one receives the value returned by the previous one.
+    /// `prelude` runs only when the observation is made, right before the first delegate, e.g. [`Self::sequenced`]
+    fn observe(
+        &mut self,
+        kind: ObservationKind,
+        ident: &Ident,
+        value: proc_macro2::TokenStream,
+        prelude: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let var_name = ident.to_string();
+        let start = ident.span().start();
+        self.points.push(PointEntry {
+            variable: var_name.clone(),
+            kind: kind.as_str().to_string(),
+            type_name: self.types.get(&var_name).cloned(),
+            line: start.line,
+            // column!() is 1-based
+            column: start.column + 1,
+        });
+
+        let observed = self.observed(kind, ident);
+        let contexts = observed
+            .and_then(|observed| observed.contexts.as_ref())
+            .unwrap_or(&self.context);
+        // evaluated after the callsite interest, before the value reaches any context
+        let condition = self.condition(kind, ident);
+        let variant = match kind {
+            ObservationKind::Register => quote!(Register),
+            ObservationKind::Propose => quote!(Propose),
+            ObservationKind::Request => quote!(Request),
+        };
+        let delegated = contexts.0.iter().fold(quote!(__rx_value), |value, context| match kind {
+            ObservationKind::Register => quote! {
+                #context.register(#value, __RX_CALLSITE.fn_name(), __RX_CALLSITE.ident_name(), __rx_type)
+            },
+            ObservationKind::Propose if self.provenance => quote! {
+                #context.propose_sourced(#value, __RX_CALLSITE.fn_name(), __RX_CALLSITE.ident_name(), &__rx_provenance)
+            },
+            ObservationKind::Propose => quote! {
+                #context.propose(#value, __RX_CALLSITE.fn_name(), __RX_CALLSITE.ident_name())
+            },
+            ObservationKind::Request => quote! {
+                #context.request(#value, __RX_CALLSITE.fn_name(), __RX_CALLSITE.ident_name())
+            },
+        });
+        let call = match kind {
+            ObservationKind::Register => quote! {
+                {
+                    let __rx_value = #value;
+                    let __rx_type = std::any::type_name_of_val(&__rx_value);
+                    #delegated
+                }
+            },
+            _ => quote! {
+                {
+                    let __rx_value = #value;
+                    #prelude
+                    #delegated
+                }
+            },
+        };
+        let callsite = self.callsite(variant, &var_name, ident.span());
+        quote! {
+            {
+                #callsite
+                if __RX_CALLSITE.is_enabled() #condition {
+                    #call
+                } else {
+                    #value
+                }
+            }
+        }
+    }
+
+    /// the static `__RX_CALLSITE` of an observation point and its registration
+    fn callsite(&self, variant: proc_macro2::TokenStream, name: &str, span: Span) -> proc_macro2::TokenStream {
+        let fn_name = &self.fn_name;
+        // spanned at the observed source, so the callsite points into the decorated function
+        let line = quote_spanned! {span=> line!()};
+        let column = quote_spanned! {span=> column!()};
+        quote! {
+            static __RX_CALLSITE: ::rx_observer::callsite::Callsite = ::rx_observer::callsite::Callsite::new(
+                ::rx_observer::callsite::Kind::#variant,
+                #fn_name,
+                #name,
+                module_path!(),
+                file!(),
+                #line,
+                #column,
+            );
+            ::rx_observer::__private::inventory::submit! {
+                ::rx_observer::registry::Registration(&__RX_CALLSITE)
+            }
+        }
+    }
+
+    /// sends the `Debug` formatted `value` of the sub-expression `text` to the `trace` delegate of every context
+    fn trace_point(&self, text: &str, span: Span, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let callsite = self.callsite(quote!(Trace), text, span);
+        let condition = self.when.as_ref().map(|when| quote!(&& (#when)));
+        let traces = self.context.0.iter().map(|context| {
+            quote! {
+                #context.trace(__RX_CALLSITE.ident_name(), __RX_CALLSITE.fn_name(), &__rx_traced);
+            }
+        });
+        quote! {
+            {
+                #callsite
+                if __RX_CALLSITE.is_enabled() #condition {
+                    #[allow(unused_imports)]
+                    use ::rx_observer::capture::{CaptureDebug as _, CaptureFallback as _};
+                    let __rx_traced = (&::rx_observer::capture::Captured(#value)).capture();
+                    #(#traces)*
+                }
+            }
+        }
+    }
+
+    /// wraps a folded `if` or `match` whose branches report themselves with [`Self::taken_branch`]:
+    /// the values of the observed variables in `condition` are taken before it is evaluated,
+    /// so an `if let` or a `match` moving them doesn't matter
+    fn decision_point(&mut self, condition: &Expr, span: Span, decision: Expr) -> Expr {
+        let condition_text = text(condition);
+        let start = span.start();
+        self.points.push(PointEntry {
+            variable: condition_text.clone(),
+            kind: String::from("decision"),
+            type_name: None,
+            line: start.line,
+            column: start.column + 1,
+        });
+        let mut mentioned = MentionedIdents::default();
+        mentioned.visit_expr(condition);
+        let values = self.observed_values(&mentioned);
+        let callsite = self.callsite(quote!(Decision), &condition_text, span);
+        let when = self.when.as_ref().map(|when| quote!(&& (#when)));
+        parse_quote! {
+            {
+                #callsite
+                let __rx_decision = __RX_CALLSITE.is_enabled() #when;
+                let __rx_decision_values: ::std::vec::Vec<(&'static str, ::std::string::String)> = if __rx_decision {
+                    ::std::vec![#(#values),*]
+                } else {
+                    ::std::vec::Vec::new()
+                };
+                #decision
+            }
+        }
+    }
+
+    /// the report of a branch, placed first in its body
+    fn taken_branch(&self, branch: &str) -> proc_macro2::TokenStream {
+        let decisions = self.context.0.iter().map(|context| {
+            quote! {
+                #context.decision(__RX_CALLSITE.ident_name(), __RX_CALLSITE.fn_name(), #branch, &__rx_decision_values);
+            }
+        });
+        quote! {
+            if __rx_decision {
+                #(#decisions)*
+            }
+        }
+    }
+
+    /// reports the error held by the `Result` or `Option` `value` refers to, if any
+    fn error_point(&mut self, expression: &str, span: Span, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let start = span.start();
+        self.points.push(PointEntry {
+            variable: expression.to_string(),
+            kind: String::from("error"),
+            type_name: None,
+            line: start.line,
+            column: start.column + 1,
+        });
+        let callsite = self.callsite(quote!(Error), expression, span);
+        let when = self.when.as_ref().map(|when| quote!(&& (#when)));
+        let errors = self.context.0.iter().map(|context| {
+            quote! {
+                #context.error(__RX_CALLSITE.ident_name(), __RX_CALLSITE.fn_name(), &__rx_error);
+            }
+        });
+        quote! {
+            {
+                #callsite
+                if __RX_CALLSITE.is_enabled() #when {
+                    #[allow(unused_imports)]
+                    use ::rx_observer::capture::{ErrorDebug as _, ErrorDisplay as _, ErrorFallback as _, ErrorOpaque as _};
+                    if let ::std::option::Option::Some(__rx_error) = (&&&&::rx_observer::capture::CapturedError(#value)).error() {
+                        #(#errors)*
+                    }
+                }
+            }
+        }
+    }
+
+    /// the scope-end guard of the observed binding `ident`, declared right after it and dropped right before it;
+    /// the guard of a binding declared without a value (`let b;`) gets one at the first assignment
+    fn binding_guard(&mut self, ident: &Ident, initialized: bool) -> Stmt {
+        let name = ident.to_string();
+        let start = ident.span().start();
+        self.points.push(PointEntry {
+            variable: name.clone(),
+            kind: String::from("scope end"),
+            type_name: self.types.get(&name).cloned(),
+            line: start.line,
+            column: start.column + 1,
+        });
+        self.scoped.push(ident.clone());
+        let guard = format_ident!("__rx_scope_{}", ident);
+        let callsite = self.callsite(quote!(ScopeEnd), &name, ident.span());
+        let update = if initialized { self.scope_update(ident) } else { quote!() };
+        let reports = self.context.0.iter().map(|context| {
+            quote! {
+                #context.scope_end(__RX_CALLSITE.ident_name(), __RX_CALLSITE.fn_name(), __rx_final);
+            }
+        });
+        parse_quote! {
+            #[allow(unused_mut)]
+            let mut #guard = {
+                #callsite
+                let mut #guard = ::rx_observer::binding::BindingGuard::new(&__RX_CALLSITE, |__rx_final: &str| {
+                    #(#reports)*
+                });
+                #update
+                #guard
+            };
+        }
+    }
+
+    /// under `provenance = true`, the `rx_observer::Provenance` of a proposal of `ident` computed by `source`,
+    /// stamped with the next sequence number only when the proposal is made
+    fn sequenced(&self, ident: &Ident, source: &Expr) -> proc_macro2::TokenStream {
+        if !self.provenance {
+            return quote!();
+        }
+        let mut mentioned = MentionedIdents::default();
+        mentioned.visit_expr(source);
+        let sources = mentioned
+            .0
+            .iter()
+            .filter(|mentioned| self.is_observed_any(mentioned))
+            .map(|mentioned| {
+                let name = mentioned.to_string();
+                if self.is_observed(ObservationKind::Propose, mentioned) {
+                    let sequence = format_ident!("__rx_seq_{}", mentioned);
+                    quote!((#name, #sequence))
+                } else {
+                    quote!((#name, ::std::option::Option::None))
+                }
+            });
+        let expression = text(source);
+        let sequence = format_ident!("__rx_seq_{}", ident);
+        quote! {
+            let __rx_provenance = ::rx_observer::Provenance {
+                sequence: ::rx_observer::provenance::next_sequence(),
+                expression: #expression,
+                sources: &[#(#sources),*],
+            };
+            #sequence = ::std::option::Option::Some(__rx_provenance.sequence);
+        }
+    }
+
+    /// keeps the value of `ident` in its scope-end guard, if it has one
+    fn scope_update(&self, ident: &Ident) -> proc_macro2::TokenStream {
+        if !self.scoped.contains(ident) {
+            return quote!();
+        }
+        let guard = format_ident!("__rx_scope_{}", ident);
+        match &self.when {
+            Some(when) => quote! {
+                if #when {
+                    #guard.update(&#ident);
+                }
+            },
+            None => quote!(#guard.update(&#ident);),
+        }
+    }
+
+    /// the `step` event of `stmt`, reported before it runs
+    fn step_point(&mut self, stmt: &Stmt) -> Stmt {
+        let statement = statement_text(stmt);
+        let span = syn::spanned::Spanned::span(stmt);
+        let start = span.start();
+        self.points.push(PointEntry {
+            variable: statement.clone(),
+            kind: String::from("step"),
+            type_name: None,
+            line: start.line,
+            column: start.column + 1,
+        });
+        let callsite = self.callsite(quote!(Step), &statement, span);
+        let condition = self.when.as_ref().map(|when| quote!(&& (#when)));
+        let steps = self.context.0.iter().map(|context| {
+            quote! {
+                #context.step(__RX_CALLSITE.ident_name(), __RX_CALLSITE.fn_name(), __RX_CALLSITE.line());
+            }
+        });
+        parse_quote! {
+            {
+                #callsite
+                if __RX_CALLSITE.is_enabled() #condition {
+                    #(#steps)*
+                }
+            }
+        }
+    }
+
+    /// `stmt` with its intermediate values traced, or `None` for statements traced through their nested statements,
+    /// e.g. `if`, `match` and loops
+    fn traced(&mut self, stmt: &Stmt) -> Option<Stmt> {
+        fn traceable(expr: &Expr) -> bool {
+            matches!(expr, Expr::Binary(_) | Expr::Call(_) | Expr::MethodCall(_) | Expr::Unary(_) | Expr::Paren(_))
+        }
+
+        let points = RefCell::new(Vec::new());
+        let sink = |text: &str, span: Span, value: proc_macro2::TokenStream| {
+            let start = span.start();
+            points.borrow_mut().push(PointEntry {
+                variable: text.to_string(),
+                kind: String::from("trace"),
+                type_name: None,
+                line: start.line,
+                column: start.column + 1,
+            });
+            self.trace_point(text, span, value)
+        };
+        let mut folder = CapturingFolder::new(&sink);
+        let traced = match stmt {
+            Stmt::Local(local) => {
+                let init = local.init.as_ref().filter(|init| traceable(&init.expr))?;
+                let mut local = local.clone();
+                local.init = Some(syn::LocalInit {
+                    expr: Box::new(folder.fold_root(*init.expr.clone())),
+                    ..init.clone()
+                });
+                Stmt::Local(local)
+            }
+            Stmt::Expr(Expr::Assign(assign), semi) if traceable(&assign.right) => {
+                let mut assign = assign.clone();
+                assign.right = Box::new(folder.fold_root(*assign.right));
+                Stmt::Expr(Expr::Assign(assign), *semi)
+            }
+            Stmt::Expr(expr, semi) if traceable(expr) => Stmt::Expr(folder.fold_root(expr.clone()), *semi),
+            _ => return None,
+        };
+        self.points.extend(points.into_inner());
+        Some(traced)
+    }
+}
+
+/// a block written as the body of a branch, which the report of the branch can go into
+fn is_block(body: &Expr) -> bool {
+    matches!(body, Expr::Block(block) if block.label.is_none())
+}
+
+/// the folded `body` of a branch with the `report` of the branch placed first, inside the block of the body
+/// when it was written as one; the instrumentation of other bodies may be a block too, with its own callsite
+fn reported(body: Expr, block: bool, report: proc_macro2::TokenStream) -> Expr {
+    match body {
+        Expr::Block(mut body) if block => {
+            body.block.stmts.insert(0, parse_quote!(#report));
+            Expr::Block(body)
+        }
+        body => parse_quote!({ #report #body }),
+    }
+}
+
+/// the identifier bound by `let x` or `let x: T`
+fn local_ident(pat: &Pat) -> Option<&Ident> {
+    match pat {
+        Pat::Ident(p) => Some(&p.ident),
+        Pat::Type(PatType { pat, .. }) => match pat.deref() {
+            Pat::Ident(p) => Some(&p.ident),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// the source text of a statement as reported by `step_trace`;
+/// control flow is reported by its head, the statements of its body follow as their own steps
+fn statement_text(stmt: &Stmt) -> String {
+    let Stmt::Expr(expr, _) = stmt else {
+        return text(stmt);
+    };
+    match expr {
+        Expr::If(ExprIf { cond, .. }) => format!("if {}", text(cond)),
+        Expr::While(ExprWhile { cond, .. }) => format!("while {}", text(cond)),
+        Expr::ForLoop(ExprForLoop { pat, expr, .. }) => format!("for {} in {}", text(pat), text(expr)),
+        Expr::Loop(_) => String::from("loop"),
+        Expr::Match(ExprMatch { expr, .. }) => format!("match {}", text(expr)),
+        _ => text(stmt),
+    }
 }
 
 impl Fold for DecoratingFolder {
     /// A Rust expression.
     fn fold_expr(&mut self, expr: Expr) -> Expr {
-        let self_context = self.context.clone();
-        let fn_name = &self.fn_name.to_owned();
-
         match expr {
             // A path like `std::mem::replace` possibly containing generic
             // parameters and a qualified self-type.
@@ -125,21 +1034,15 @@ impl Fold for DecoratingFolder {
             // A plain identifier like `x` is a path of length 1.
             Expr::Path(expr_path) => {
                 if let Some(ident) = expr_path.path.get_ident() {
-                    let var_name = ident.to_string();
-                    let is_register = self.register.contains(ident);
-                    let is_request = self.request.contains(ident);
-                    // let typ = expr_path.path.segments.last()
+                    let is_register = self.is_observed(ObservationKind::Register, ident);
+                    let is_request = self.is_observed(ObservationKind::Request, ident);
                     if is_register {
-                        let output = quote! {
-                            #self_context.register(#expr_path, #fn_name, #var_name, std::any::type_name_of_val(&#expr_path))
-                        };
+                        let output = self.observe(ObservationKind::Register, ident, quote!(#expr_path), quote!());
                         return syn::parse2(output)
                             .expect("Failed to parse transformed register expression");
                     }
                     if is_request {
-                        let output = quote! {
-                            #self_context.request(#expr_path, #fn_name, #var_name)
-                        };
+                        let output = self.observe(ObservationKind::Request, ident, quote!(#expr_path), quote!());
                         return syn::parse2(output)
                             .expect("Failed to parse transformed request expression");
                     }
@@ -151,68 +1054,140 @@ impl Fold for DecoratingFolder {
             //
             // An assignment expression: `a = compute()`.
             Expr::Assign(expr_assign) => {
-                let folded_assign = self.fold_expr_assign(expr_assign);
-
-                let left = folded_assign.left.clone();
-                let ident = if let Expr::Path(ExprPath { path, .. }) = left.deref() {
-                    path.get_ident()
+                let ident = if let Expr::Path(ExprPath { path, .. }) = expr_assign.left.deref() {
+                    path.get_ident().cloned()
                 } else {
                     None
                 };
-                let output = if ident.is_some_and(|id| self.propose.contains(id)) {
-                    let var_name = ident.map(|id| id.to_string()).unwrap_or(String::from("_"));
-                    let proposal_call = quote! {
-                        #self_context.propose(#left, #fn_name, #var_name);
-                    };
-                    quote! {
-                        {
-                            #folded_assign;
-                            #proposal_call
+                let assigned = ident.clone();
+                let output = match ident {
+                    // a single assignment of the proposed value, so a deferred-init `let` is assigned once;
+                    // the value is bound to the variable's name while proposed, as with a proposed `let`
+                    Some(ident) if self.is_observed(ObservationKind::Propose, &ident) => {
+                        let left = &expr_assign.left;
+                        let right = *expr_assign.right.clone();
+                        let sequenced = self.sequenced(&ident, &right);
+                        let right = self.fold_expr(right);
+                        let proposal = self.observe(ObservationKind::Propose, &ident, quote!(#ident), sequenced);
+                        let checks = self.checks_after(&ident);
+                        let scope_update = self.scope_update(&ident);
+                        quote! {
+                            {
+                                #left = {
+                                    let #ident = #right;
+                                    #proposal
+                                };
+                                #checks
+                                #scope_update
+                            }
                         }
                     }
-                } else {
-                    quote! {#folded_assign}
+                    _ => {
+                        let folded_assign = self.fold_expr_assign(expr_assign);
+                        quote! {#folded_assign}
+                    }
                 };
+                // a `let` declared without a value is bound in the rest of the block by its assignment
+                if let Some(assigned) = assigned
+                    && self.locals.contains(&assigned)
+                    && !self.bound.contains(&assigned)
+                {
+                    self.bound.push(assigned);
+                }
 
                 syn::parse2(output).expect("Failed to parse transformed assignment block")
             }
 
-            Expr::Call(expr_call) => {
-                let func = Box::new(self.fold_expr(*expr_call.func));
-                // let fn_name = quote!(#func).to_string();
-
-                let args: Punctuated<Expr, Token![,]> = expr_call
-                    .args
-                    .into_iter()
-                    .map(|arg| -> Expr {
-                        // Recursively transform the argument first
-                        let transformed_arg = self.fold_expr(arg);
-                        let var_ident = match &transformed_arg {
-                            Expr::Path(ExprPath { path, .. })
-                                if path.segments.len() == 1
-                                    && path.leading_colon.is_none()
-                                    && path.segments[0].arguments.is_empty() =>
-                            {
-                                Some(path.segments[0].ident.clone())
-                            }
-                            _ => None,
-                        };
-                        if var_ident.is_some_and(|ident| self.request.contains(&ident)) {
-                            syn::parse_quote! { #self_context.request(#transformed_arg) }
-                        } else {
-                            transformed_arg
+            // `if cond { .. } else { .. }`, a missing `else` is added to report it
+            Expr::If(expr_if) if self.decisions => {
+                let condition = (*expr_if.cond).clone();
+                let span = expr_if.if_token.span;
+                let else_block = matches!(&expr_if.else_branch, Some((_, else_expr)) if is_block(else_expr));
+                let mut folded = fold::fold_expr_if(self, expr_if);
+                let then_report = self.taken_branch("then");
+                folded.then_branch.stmts.insert(0, parse_quote!(#then_report));
+                let else_report = self.taken_branch("else");
+                let else_branch = match folded.else_branch.take() {
+                    Some((_, else_expr)) => reported(*else_expr, else_block, else_report),
+                    None => parse_quote!({ #else_report }),
+                };
+                folded.else_branch = Some((Default::default(), Box::new(else_branch)));
+                self.decision_point(&condition, span, Expr::If(folded))
+            }
+
+            // `match scrutinee { pattern if guard => .. }`
+            Expr::Match(expr_match) if self.decisions => {
+                let scrutinee = (*expr_match.expr).clone();
+                let span = expr_match.match_token.span;
+                let blocks = expr_match.arms.iter().map(|arm| is_block(&arm.body)).collect::<Vec<_>>();
+                let mut folded = fold::fold_expr_match(self, expr_match);
+                for (arm, block) in folded.arms.iter_mut().zip(blocks) {
+                    let pat = &arm.pat;
+                    let branch = match &arm.guard {
+                        Some((_, guard)) => format!("{} if {}", text(pat), text(guard)),
+                        None => text(pat),
+                    };
+                    let report = self.taken_branch(&branch);
+                    let body = std::mem::replace(&mut *arm.body, Expr::Verbatim(quote!()));
+                    *arm.body = reported(body, block, report);
+                    arm.comma = Some(Default::default());
+                }
+                self.decision_point(&scrutinee, span, Expr::Match(folded))
+            }
+
+            // `?` and `return` in closures and async blocks leave them, not the function
+            Expr::Closure(_) | Expr::Async(_) if !self.nested => {
+                self.nested = true;
+                let folded = self.fold_expr(expr);
+                self.nested = false;
+                folded
+            }
+
+            // `expr?`
+            Expr::Try(expr_try) if self.errors && !self.nested => {
+                let expression = text(&expr_try.expr);
+                let span = syn::spanned::Spanned::span(&expr_try.expr);
+                let ExprTry { expr: tried, .. } = fold::fold_expr_try(self, expr_try);
+                let report = self.error_point(&expression, span, quote!(&__rx_tried));
+                parse_quote! {
+                    match #tried {
+                        __rx_tried => {
+                            #report
+                            __rx_tried?
                         }
-                    })
-                    .collect();
-
-                Expr::Call(ExprCall {
-                    attrs: expr_call.attrs,
-                    func,
-                    paren_token: expr_call.paren_token,
-                    args,
-                })
+                    }
+                }
             }
 
+            // `return Err(..)`
+            Expr::Return(ExprReturn { expr: Some(returned), .. })
+                if self.errors
+                    && !self.nested
+                    && self.return_type.is_some()
+                    && matches!(returned.deref(), Expr::Call(ExprCall { func, .. })
+                        if matches!(func.deref(), Expr::Path(ExprPath { path, .. })
+                            if path.segments.last().is_some_and(|segment| segment.ident == "Err"))) =>
+            {
+                let expression = text(&returned);
+                let span = syn::spanned::Spanned::span(&returned);
+                let returned = self.fold_expr(*returned);
+                let report = self.error_point(&expression, span, quote!(&__rx_returned));
+                let return_type = &self.return_type;
+                parse_quote! {
+                    return {
+                        let __rx_returned: #return_type = #returned;
+                        #report
+                        __rx_returned
+                    }
+                }
+            }
+
+            // `assert!(...)` in expression position
+            Expr::Macro(expr_macro) if self.power_assert => match power_assert::rewrite(&expr_macro.mac, &|ident| self.is_observed_any(ident)) {
+                Some(output) => syn::parse2(output).expect("Failed to parse rewritten assertion"),
+                None => Expr::Macro(expr_macro),
+            },
+
             _ => fold::fold_expr(self, expr),
         }
     }
@@ -242,36 +1217,87 @@ impl Fold for DecoratingFolder {
         }
     }
 
+    /// A braced block containing Rust statements.
+    fn fold_block(&mut self, block: Block) -> Block {
+        let (scoped, bound) = (self.scoped.len(), self.bound.len());
+        let mut stmts = Vec::with_capacity(block.stmts.len() * 2);
+        for stmt in block.stmts {
+            // items are declarations, not steps
+            if self.step_trace && !matches!(stmt, Stmt::Item(_)) {
+                stmts.push(self.step_point(&stmt));
+            }
+            let guarded = match &stmt {
+                Stmt::Local(local) if self.scope_end => local_ident(&local.pat)
+                    .filter(|ident| self.is_observed_any(ident))
+                    .map(|ident| (ident.clone(), local.init.is_some())),
+                _ => None,
+            };
+            let binds = match &stmt {
+                Stmt::Local(local) if local.init.is_some() => pat_idents(&local.pat),
+                _ => Vec::new(),
+            };
+            stmts.push(self.fold_stmt(stmt));
+            self.bound.extend(binds);
+            if let Some((ident, initialized)) = guarded {
+                stmts.push(self.binding_guard(&ident, initialized));
+            }
+        }
+        self.scoped.truncate(scoped);
+        self.bound.truncate(bound);
+        Block { stmts, ..block }
+    }
+
     /// A statement, usually ending in a semicolon.
     fn fold_stmt(&mut self, s: Stmt) -> Stmt {
-        let fn_name = &self.fn_name.to_owned();
+        // the traced statement is observed as usual, without tracing or stepping through the instrumentation itself
+        if self.trace_all
+            && let Some(traced) = self.traced(&s)
+        {
+            let step_trace = std::mem::replace(&mut self.step_trace, false);
+            self.trace_all = false;
+            let folded = self.fold_stmt(traced);
+            self.trace_all = true;
+            self.step_trace = step_trace;
+            return folded;
+        }
         match s {
             Stmt::Local(local_let_stmt) => {
-                if local_let_stmt.init.is_some() {
-                    let Local { pat, init, .. } = local_let_stmt.clone();
-                    let init = self.fold_expr(*init.unwrap().expr);
-                    let ident = match pat {
-                        Pat::Ident(ref p) => &p.ident,
-                        _ => unreachable!(),
-                    };
-                    if self.propose.contains(ident) {
-                        let var_name = ident.to_string();
-                        let self_context = self.context.clone();
+                // `let x = ...` or `let x: T = ...`
+                if let Pat::Type(PatType { pat, ty, .. }) = &local_let_stmt.pat
+                    && let Pat::Ident(p) = pat.deref()
+                {
+                    self.types.insert(p.ident.to_string(), quote!(#ty).to_string());
+                }
+                let ident = local_ident(&local_let_stmt.pat).cloned();
+                match ident {
+                    Some(ident) if local_let_stmt.init.is_some() && self.is_observed(ObservationKind::Propose, &ident) => {
+                        let Local { pat, init, .. } = local_let_stmt;
+                        let init = *init.unwrap().expr;
+                        let sequenced = self.sequenced(&ident, &init);
+                        let init = self.fold_expr(init);
+                        let proposal = self.observe(ObservationKind::Propose, &ident, quote!(#ident), sequenced);
+                        let checks = self.checks_after(&ident);
                         parse_quote! {
                             let #pat = {
                                 #[allow(unused_mut)]
                                 let #pat = #init;
-                                #self_context.propose(#ident, #fn_name, #var_name);
+                                let #ident = #proposal;
+                                #checks
                                 #ident
                             };
                         }
-                    } else {
-                        Stmt::Local(fold::fold_local(self, local_let_stmt))
                     }
-                } else {
-                    Stmt::Local(fold::fold_local(self, local_let_stmt))
+                    _ => Stmt::Local(fold::fold_local(self, local_let_stmt)),
                 }
             }
+            // `assert!(...);`
+            Stmt::Macro(stmt_macro) if self.power_assert => match power_assert::rewrite(&stmt_macro.mac, &|ident| self.is_observed_any(ident)) {
+                Some(output) => Stmt::Expr(
+                    syn::parse2(output).expect("Failed to parse rewritten assertion"),
+                    stmt_macro.semi_token,
+                ),
+                None => Stmt::Macro(stmt_macro),
+            },
             _ => fold::fold_stmt(self, s),
         }
     }
@@ -0,0 +1,182 @@
+//! JSON manifest of the observation points of a crate, written at build time.
+//!
+//! The manifest goes to `$RX_OBSERVER_MANIFEST_DIR` or, when the crate has a build script, to `$OUT_DIR`
+//! as `<crate name>.rx_observer.json`. A compilation replaces the entries of every source file it decorates
+//! functions in, so the entries of a function moved or removed from its file are dropped by the next build;
+//! a file no longer decorating anything keeps its entries until a clean build.
+//!
+//! A compilation is told apart by its manifest and its kind of target (library, binary, integration test), not by
+//! the process: a long-lived expansion process such as an editor's proc-macro server expands for every crate of a
+//! workspace, and replaces the entries of a file once per compilation it expands for. Entries it leaves stale, e.g.
+//! a function edited to another line while the process runs, are replaced by the next build. The unit tests of a
+//! library share its manifest and kind, so a file's entries are those of whichever was compiled last.
+//!
+//! Several compilations of a crate may run at once (e.g. its library and its tests), so the manifest is updated
+//! under an exclusive lock of `<crate name>.rx_observer.json.lock` and replaced atomically.
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+#[derive(Serialize, Deserialize, Default)]
+struct Manifest {
+    #[serde(rename = "crate")]
+    crate_name: String,
+    functions: Vec<FunctionEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct FunctionEntry {
+    pub(crate) function: String,
+    pub(crate) file: String,
+    pub(crate) line: usize,
+    pub(crate) points: Vec<PointEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PointEntry {
+    pub(crate) variable: String,
+    pub(crate) kind: String,
+    /// the type annotation of a `let` or a function parameter, when there is one
+    #[serde(rename = "type")]
+    pub(crate) type_name: Option<String>,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+impl Manifest {
+    /// replaces the entry of the same function, file and line, or every entry of the file on its first entry
+    /// in this compilation
+    fn upsert(&mut self, entry: FunctionEntry, first_in_file: bool) {
+        self.functions.retain(|f| {
+            f.file != entry.file || !(first_in_file || (f.function == entry.function && f.line == entry.line))
+        });
+        self.functions.push(entry);
+        self.functions
+            .sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    }
+}
+
+fn manifest_path() -> Option<PathBuf> {
+    let dir = env::var_os("RX_OBSERVER_MANIFEST_DIR").or_else(|| env::var_os("OUT_DIR"))?;
+    let crate_name = env::var("CARGO_CRATE_NAME").unwrap_or_else(|_| String::from("unknown"));
+    Some(PathBuf::from(dir).join(format!("{crate_name}.rx_observer.json")))
+}
+
+/// the source files whose entries of earlier builds were dropped, with the compilation that dropped them;
+/// a process may expand for several compilations, e.g. an editor's proc-macro server for every crate of a workspace
+static REPLACED_FILES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// the compilation being expanded: its manifest and its kind of target, read from the variables cargo sets for it
+fn compilation(path: &std::path::Path) -> String {
+    let kind = match env::var("CARGO_BIN_NAME") {
+        Ok(bin_name) => format!("bin {bin_name}"),
+        // only set for integration tests and benches
+        Err(_) if env::var_os("CARGO_TARGET_TMPDIR").is_some() => String::from("test"),
+        Err(_) => String::from("lib"),
+    };
+    format!("{} {kind}", path.display())
+}
+
+/// whether `file` is seen for the first time in `compilation`, which then replaces its entries
+fn first_in_file(compilation: String, file: &str) -> bool {
+    let mut replaced = REPLACED_FILES.lock().unwrap_or_else(|e| e.into_inner());
+    let key = (compilation, file.to_string());
+    let first = !replaced.contains(&key);
+    if first {
+        replaced.push(key);
+    }
+    first
+}
+
+/// upserts the entry of a decorated function, keyed by its name, file and line,
+/// so same-named functions of different `impl` blocks or modules of a file keep their own entries;
+/// the manifest is best effort and never fails the build
+pub(crate) fn record(entry: FunctionEntry) {
+    let Some(path) = manifest_path() else {
+        return;
+    };
+    // released when the file is closed
+    let Ok(lock) = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.with_extension("json.lock"))
+    else {
+        return;
+    };
+    if lock.lock().is_err() {
+        return;
+    }
+    let mut manifest: Manifest = fs::read_to_string(&path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    manifest.crate_name = env::var("CARGO_CRATE_NAME").unwrap_or_default();
+    let first_in_file = first_in_file(compilation(&path), &entry.file);
+    manifest.upsert(entry, first_in_file);
+
+    let Ok(json) = serde_json::to_string_pretty(&manifest) else {
+        return;
+    };
+    // rename is atomic, so a reader without the lock never sees a half-written manifest
+    let tmp_path = path.with_extension(format!("json.{}", std::process::id()));
+    if fs::write(&tmp_path, json).is_ok() {
+        let _ = fs::rename(&tmp_path, &path);
+    }
+    drop(lock);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(function: &str, file: &str, line: usize) -> FunctionEntry {
+        FunctionEntry {
+            function: function.to_string(),
+            file: file.to_string(),
+            line,
+            points: Vec::new(),
+        }
+    }
+
+    fn lines(manifest: &Manifest) -> Vec<(&str, usize)> {
+        manifest
+            .functions
+            .iter()
+            .map(|f| (f.function.as_str(), f.line))
+            .collect()
+    }
+
+    #[test]
+    fn same_named_functions_of_a_file_keep_their_entries() {
+        let mut manifest = Manifest::default();
+        manifest.upsert(entry("new", "src/orders.rs", 10), true);
+        manifest.upsert(entry("new", "src/orders.rs", 40), false);
+        manifest.upsert(entry("new", "src/orders.rs", 10), false);
+        assert_eq!(lines(&manifest), [("new", 10), ("new", 40)]);
+    }
+
+    #[test]
+    fn a_new_compilation_drops_the_stale_entries_of_a_file() {
+        let mut manifest = Manifest::default();
+        manifest.upsert(entry("quote", "src/orders.rs", 10), true);
+        manifest.upsert(entry("total", "src/orders.rs", 20), false);
+        manifest.upsert(entry("load", "src/main.rs", 5), true);
+        // `quote` moved down by a line
+        manifest.upsert(entry("quote", "src/orders.rs", 11), true);
+        assert_eq!(lines(&manifest), [("load", 5), ("quote", 11)]);
+    }
+
+    #[test]
+    fn every_compilation_replaces_a_file_once() {
+        let library = String::from("target/out/shop.rx_observer.json lib");
+        let binary = String::from("target/out/shop.rx_observer.json bin shop");
+        assert!(first_in_file(library.clone(), "src/orders.rs"));
+        assert!(!first_in_file(library.clone(), "src/orders.rs"));
+        assert!(first_in_file(library, "src/lib.rs"));
+        assert!(first_in_file(binary.clone(), "src/orders.rs"));
+        assert!(!first_in_file(binary, "src/orders.rs"));
+    }
+}
@@ -0,0 +1,142 @@
+//! `power_assert = true`: rewrites `assert!`, `assert_eq!` and `assert_ne!`
+//! so that a failure shows the value of every sub-expression and of the observed variables it mentions
+//! (see `rx_observer::assertion`).
+//!
+//! A passing assertion costs what the original one does: the operands are evaluated as written, and only a failing
+//! assertion evaluates its instrumented operands a second time to capture their sub-expressions.
+use crate::MentionedIdents;
+use crate::capture::{is_place, text, CapturingFolder};
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{quote, quote_spanned};
+use std::cell::RefCell;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{Expr, Macro, Token};
+
+/// records a captured value into `__rx_captures`
+fn capture(text: &str, _span: Span, value: TokenStream2) -> TokenStream2 {
+    quote! {
+        __rx_captures.push((#text, (&::rx_observer::capture::Captured(#value)).capture()));
+    }
+}
+
+/// the operands instrumented for the failure report, and the texts of the sub-expressions they capture
+struct Instrumented {
+    captured: RefCell<Vec<String>>,
+}
+
+impl Instrumented {
+    fn capture(&self, text: &str, span: Span, value: TokenStream2) -> TokenStream2 {
+        self.captured.borrow_mut().push(text.to_string());
+        capture(text, span, value)
+    }
+
+    /// `operand` re-evaluated with its sub-expressions captured, its value discarded
+    fn reevaluated(&self, operand: &Expr) -> TokenStream2 {
+        let sink = |text: &str, span: Span, value: TokenStream2| self.capture(text, span, value);
+        let operand = CapturingFolder::new(&sink).fold_root(operand.clone());
+        quote!(let _ = &(#operand);)
+    }
+
+    /// the captures of the observed variables mentioned by the operands and not captured as sub-expressions,
+    /// e.g. the ones in closures or method receivers
+    fn observed<'e>(&self, operands: impl IntoIterator<Item = &'e Expr>, is_observed: &dyn Fn(&Ident) -> bool) -> TokenStream2 {
+        let mut mentioned = MentionedIdents::default();
+        operands.into_iter().for_each(|operand| mentioned.visit_expr(operand));
+        let captured = self.captured.borrow();
+        let observed = mentioned
+            .0
+            .iter()
+            .filter(|ident| is_observed(ident) && !captured.contains(&ident.to_string()))
+            .map(|ident| capture(&ident.to_string(), ident.span(), quote!(&#ident)));
+        quote!(#(#observed)*)
+    }
+}
+
+/// the rewritten assertion, or `None` for other macros and assertions that don't parse;
+/// `is_observed` tells the variables observed by the decorated function
+pub(crate) fn rewrite(mac: &Macro, is_observed: &dyn Fn(&Ident) -> bool) -> Option<TokenStream2> {
+    let name = mac.path.get_ident()?.to_string();
+    let args = mac
+        .parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+        .ok()?;
+    let mut args = args.into_iter();
+    let instrumented = Instrumented {
+        captured: RefCell::new(Vec::new()),
+    };
+    // panics at the assertion, not at the attribute
+    let panic = quote_spanned! {mac.path.span()=>
+        ::std::panic!("{}", ::rx_observer::assertion::failure_message(__rx_headline, &__rx_captures))
+    };
+    let prelude = quote! {
+        #[allow(unused_imports)]
+        use ::rx_observer::capture::{CaptureDebug as _, CaptureFallback as _};
+        #[allow(unused_mut)]
+        let mut __rx_captures: ::std::vec::Vec<(&'static str, ::std::string::String)> = ::std::vec::Vec::new();
+    };
+    match name.as_str() {
+        "assert" => {
+            let condition = args.next()?;
+            let condition_text = text(&condition);
+            let message = custom_message(args);
+            let reevaluated = instrumented.reevaluated(&condition);
+            let observed = instrumented.observed([&condition], is_observed);
+            Some(quote! {
+                if !(#condition) {
+                    #prelude
+                    #reevaluated
+                    #observed
+                    let __rx_headline = ::std::format!("assertion failed: {}{}", #condition_text, #message);
+                    #panic;
+                }
+            })
+        }
+        "assert_eq" | "assert_ne" => {
+            let (left, right) = (args.next()?, args.next()?);
+            let message = custom_message(args);
+            let (op, failed) = if name == "assert_eq" {
+                ("==", quote!(!(*__rx_left == *__rx_right)))
+            } else {
+                ("!=", quote!(*__rx_left == *__rx_right))
+            };
+            let headline = format!("assertion `left {op} right` failed{{}}\n  left: {{:?}}\n right: {{:?}}");
+            // a place operand is captured through its reference, the others are evaluated again
+            let operand = |operand: &Expr, reference: TokenStream2| {
+                if is_place(operand) {
+                    instrumented.capture(&text(operand), operand.span(), reference)
+                } else {
+                    instrumented.reevaluated(operand)
+                }
+            };
+            let left_capture = operand(&left, quote!(__rx_left));
+            let right_capture = operand(&right, quote!(__rx_right));
+            let observed = instrumented.observed([&left, &right], is_observed);
+            Some(quote! {
+                match (&(#left), &(#right)) {
+                    (__rx_left, __rx_right) => {
+                        if #failed {
+                            #prelude
+                            #left_capture
+                            #right_capture
+                            #observed
+                            let __rx_headline = ::std::format!(#headline, #message, __rx_left, __rx_right);
+                            #panic;
+                        }
+                    }
+                }
+            })
+        }
+        _ => None,
+    }
+}
+
+/// `": <formatted message>"` of the optional format arguments, or `""`
+fn custom_message(args: impl Iterator<Item = Expr>) -> TokenStream2 {
+    let args = args.collect::<Vec<_>>();
+    if args.is_empty() {
+        quote!("")
+    } else {
+        quote!(::std::format!(": {}", ::std::format_args!(#(#args),*)))
+    }
+}
@@ -0,0 +1,68 @@
+//! Observations per second of a decorated hot loop run on 1 to 8 threads,
+//! all observing through one `HistoryContext` or one `BufferedHistoryContext`, whose merge counts in the total:
+//! ```text
+//! cargo bench --bench history_throughput --features history
+//! ```
+use rx_observer::history::{BufferedHistoryContext, HistoryContext};
+use rx_observer::prelude::*;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u64 = 50_000;
+
+#[decorate_vars(
+    context = param ctx,
+    propose = [total]
+)]
+fn hot_loop(iterations: u64) -> u64 {
+    let mut total = 0;
+    for i in 0..iterations {
+        total = (total + i) % 1_000_003;
+    }
+    total
+}
+
+fn run<C>(context: &C, threads: usize) -> Duration
+where
+    C: for<'a> ObserverContext<'a> + Sync,
+{
+    let start = Instant::now();
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| std::hint::black_box(hot_loop(context, ITERATIONS)));
+        }
+    });
+    start.elapsed()
+}
+
+///the throughput of observing and merging, the merge of the buffered context is also shown apart
+fn report(name: &str, threads: usize, observe: Duration, merge: Duration, records: usize) {
+    let total = observe + merge;
+    let per_second = records as f64 / total.as_secs_f64();
+    println!(
+        "{name:<24}{threads:>8}{records:>10}{:>12.1}{:>10.1}{:>10.1}{per_second:>18.0}",
+        observe.as_secs_f64() * 1000.0,
+        merge.as_secs_f64() * 1000.0,
+        total.as_secs_f64() * 1000.0
+    );
+}
+
+fn main() {
+    let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    println!("{cores} cores available, {ITERATIONS} observations per thread");
+    println!(
+        "{:<24}{:>8}{:>10}{:>12}{:>10}{:>10}{:>18}",
+        "context", "threads", "records", "observe ms", "merge ms", "total ms", "observations/s"
+    );
+    for threads in [1, 2, 4, 8] {
+        let history = HistoryContext::new();
+        let elapsed = run(&history, threads);
+        report("HistoryContext", threads, elapsed, Duration::ZERO, history.log_length());
+
+        let buffered = BufferedHistoryContext::new();
+        let elapsed = run(&buffered, threads);
+        let start = Instant::now();
+        buffered.flush();
+        let merge = start.elapsed();
+        report("BufferedHistoryContext", threads, elapsed, merge, buffered.log_length());
+    }
+}
@@ -0,0 +1,31 @@
+//! Support of `power_assert = true`: rich `assert!`/`assert_eq!`/`assert_ne!` failures.
+//!
+//! A passing assertion evaluates its operands once, as written. A failing one evaluates them a second time with
+//! the value of every sub-expression recorded, and lists them below the usual message, followed by the observed
+//! variables the assertion mentions elsewhere, e.g. in a closure:
+//! ```text
+//! assertion failed: a + b == c * 2
+//!   a = 1
+//!   b = 2
+//!   a + b = 3
+//!   c = 2
+//!   c * 2 = 4
+//!   a + b == c * 2 = false
+//! ```
+//! Values are formatted with `Debug`, the ones without it are shown as `<?>`. Since the operands of a failing
+//! assertion run twice, an operand with side effects reports the values of its second run, and one moving
+//! a variable doesn't compile: bind its value before the assertion.
+
+///the panic message: the headline followed by the captured values, repeated ones listed once
+#[doc(hidden)]
+pub fn failure_message(headline: String, captures: &[(&'static str, String)]) -> String {
+    let mut message = headline;
+    let mut listed: Vec<&(&'static str, String)> = Vec::new();
+    for capture in captures {
+        if !listed.contains(&capture) {
+            message.push_str(&format!("\n  {} = {}", capture.0, capture.1));
+            listed.push(capture);
+        }
+    }
+    message
+}
@@ -0,0 +1,50 @@
+//! Scope-end events of observed bindings, emitted with `scope_end = true`.
+//!
+//! The macro declares a [`BindingGuard`] right after each observed `let` binding. The guard keeps the latest
+//! observed value of the binding and reports it to the `scope_end` delegate when it is dropped together with
+//! the binding: at the end of the block, on an early return or `?`, or while unwinding. The guard of a binding
+//! declared without a value (`let fee;`) holds none until the first assignment, and reports nothing before it.
+use crate::callsite::Callsite;
+use std::fmt::Display;
+
+///the drop guard of an observed binding
+#[doc(hidden)]
+pub struct BindingGuard<F>
+where
+    F: FnMut(&str),
+{
+    callsite: &'static Callsite,
+    value: Option<String>,
+    report: F,
+}
+
+impl<F> BindingGuard<F>
+where
+    F: FnMut(&str),
+{
+    pub fn new(callsite: &'static Callsite, report: F) -> Self {
+        BindingGuard {
+            callsite,
+            value: None,
+            report,
+        }
+    }
+
+    ///keeps the value of the binding, unless its callsite is disabled
+    pub fn update(&mut self, value: &impl Display) {
+        if self.callsite.is_enabled() {
+            self.value = Some(value.to_string());
+        }
+    }
+}
+
+impl<F> Drop for BindingGuard<F>
+where
+    F: FnMut(&str),
+{
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            (self.report)(&value);
+        }
+    }
+}
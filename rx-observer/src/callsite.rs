@@ -0,0 +1,205 @@
+//! Static descriptors of observation points.
+//!
+//! `#[decorate_vars]` emits one `static` [`Callsite`] per observation point,
+//! so the metadata passed to an [`ObserverContext`](crate::ObserverContext) is `&'static`
+//! and a disabled point costs a single relaxed atomic load.
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+const UNREGISTERED: u8 = 0;
+const NEVER: u8 = 1;
+const ALWAYS: u8 = 2;
+
+///the delegate of `ObserverContext` an observation point is routed to
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Register,
+    Propose,
+    Request,
+    ///a `check` invariant evaluated after a proposal of a variable it mentions, named by its condition
+    Check,
+    ///a sub-expression evaluated under `trace_all = true`, named by its source text
+    Trace,
+    ///a statement about to run under `step_trace = true`, named by its source text
+    Step,
+    ///an `if` or `match` under `decisions = true`, named by its condition or scrutinee
+    Decision,
+    ///a `?` or a `return Err(..)` under `errors = true`, named by the expression producing the error
+    Error,
+    ///an observed binding going out of scope under `scope_end = true`
+    ScopeEnd,
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Kind::Register => "register",
+            Kind::Propose => "propose",
+            Kind::Request => "request",
+            Kind::Check => "check",
+            Kind::Trace => "trace",
+            Kind::Step => "step",
+            Kind::Decision => "decision",
+            Kind::Error => "error",
+            Kind::ScopeEnd => "scope end",
+        })
+    }
+}
+
+///a single observation point emitted by the macro
+pub struct Callsite {
+    kind: Kind,
+    fn_name: &'static str,
+    ident_name: &'static str,
+    module_path: &'static str,
+    file: &'static str,
+    line: u32,
+    column: u32,
+    interest: AtomicU8,
+}
+
+type Filter = Box<dyn Fn(&Callsite) -> bool + Send + Sync>;
+
+struct Registry {
+    callsites: Vec<&'static Callsite>,
+    filter: Option<Filter>,
+}
+
+impl Registry {
+    fn interest(&self, callsite: &Callsite) -> u8 {
+        match &self.filter {
+            Some(filter) if !filter(callsite) => NEVER,
+            _ => ALWAYS,
+        }
+    }
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    callsites: Vec::new(),
+    filter: None,
+});
+
+impl Callsite {
+    pub const fn new(
+        kind: Kind,
+        fn_name: &'static str,
+        ident_name: &'static str,
+        module_path: &'static str,
+        file: &'static str,
+        line: u32,
+        column: u32,
+    ) -> Self {
+        Callsite {
+            kind,
+            fn_name,
+            ident_name,
+            module_path,
+            file,
+            line,
+            column,
+            interest: AtomicU8::new(UNREGISTERED),
+        }
+    }
+
+    ///whether the observation point should call its context;
+    ///registers the callsite on its first hit
+    #[inline]
+    pub fn is_enabled(&'static self) -> bool {
+        match self.interest.load(Ordering::Relaxed) {
+            ALWAYS => true,
+            NEVER => false,
+            _ => self.register(),
+        }
+    }
+
+    #[cold]
+    fn register(&'static self) -> bool {
+        let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        // another thread may have registered it while we were waiting for the lock
+        if self.interest.load(Ordering::Relaxed) == UNREGISTERED {
+            registry.callsites.push(self);
+            self.interest
+                .store(registry.interest(self), Ordering::Relaxed);
+        }
+        self.interest.load(Ordering::Relaxed) == ALWAYS
+    }
+
+    ///overrides the interest of this callsite until the next [`set_filter`]/[`clear_filter`]
+    pub fn set_enabled(&self, enabled: bool) {
+        self.interest
+            .store(if enabled { ALWAYS } else { NEVER }, Ordering::Relaxed);
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+    pub fn fn_name(&self) -> &'static str {
+        self.fn_name
+    }
+    pub fn ident_name(&self) -> &'static str {
+        self.ident_name
+    }
+    pub fn module_path(&self) -> &'static str {
+        self.module_path
+    }
+    pub fn file(&self) -> &'static str {
+        self.file
+    }
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+    pub fn column(&self) -> u32 {
+        self.column
+    }
+}
+
+///`kind ‹fn/ident› at file:line:column`
+impl Display for Callsite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ‹{}/{}› at {}:{}:{}",
+            self.kind, self.fn_name, self.ident_name, self.file, self.line, self.column
+        )
+    }
+}
+
+///enables only the callsites the filter returns `true` for,
+///re-evaluating every callsite registered so far
+pub fn set_filter<F>(filter: F)
+where
+    F: Fn(&Callsite) -> bool + Send + Sync + 'static,
+{
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    registry.filter = Some(Box::new(filter));
+    rebuild_interest(&registry);
+}
+
+///enables every callsite again
+pub fn clear_filter() {
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    registry.filter = None;
+    rebuild_interest(&registry);
+}
+
+///visits every callsite hit so far
+pub fn for_each<F>(mut f: F)
+where
+    F: FnMut(&'static Callsite),
+{
+    let callsites = REGISTRY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .callsites
+        .clone();
+    callsites.into_iter().for_each(&mut f);
+}
+
+fn rebuild_interest(registry: &Registry) {
+    for callsite in &registry.callsites {
+        callsite
+            .interest
+            .store(registry.interest(callsite), Ordering::Relaxed);
+    }
+}
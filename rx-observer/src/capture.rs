@@ -0,0 +1,87 @@
+//! Formatting of captured sub-expression values, for `power_assert`, `trace_all` and `errors`.
+use std::fmt::{Debug, Display};
+
+///a captured sub-expression value, formatted with `Debug` when it has one
+#[doc(hidden)]
+pub struct Captured<'v, T>(pub &'v T);
+
+#[doc(hidden)]
+pub trait CaptureDebug {
+    fn capture(&self) -> String;
+}
+
+impl<T: Debug> CaptureDebug for Captured<'_, T> {
+    fn capture(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+///picked by method resolution only when `CaptureDebug` doesn't apply,
+///since it needs one more autoref
+#[doc(hidden)]
+pub trait CaptureFallback {
+    fn capture(&self) -> String;
+}
+
+impl<T> CaptureFallback for &Captured<'_, T> {
+    fn capture(&self) -> String {
+        String::from("<?>")
+    }
+}
+
+///the value a `?` is applied to, holding the error that short-circuits, if any;
+///called as `(&&&&CapturedError(&value)).error()` so that method resolution picks
+///the first of `ErrorDisplay`, `ErrorDebug`, `ErrorOpaque` and `ErrorFallback` that applies
+#[doc(hidden)]
+pub struct CapturedError<'v, T>(pub &'v T);
+
+#[doc(hidden)]
+pub trait ErrorDisplay {
+    fn error(&self) -> Option<String>;
+}
+
+impl<T, E: Display> ErrorDisplay for &&&CapturedError<'_, Result<T, E>> {
+    fn error(&self) -> Option<String> {
+        self.0.as_ref().err().map(|error| error.to_string())
+    }
+}
+
+impl<T> ErrorDisplay for &&&CapturedError<'_, Option<T>> {
+    fn error(&self) -> Option<String> {
+        self.0.is_none().then(|| String::from("None"))
+    }
+}
+
+#[doc(hidden)]
+pub trait ErrorDebug {
+    fn error(&self) -> Option<String>;
+}
+
+impl<T, E: Debug> ErrorDebug for &&CapturedError<'_, Result<T, E>> {
+    fn error(&self) -> Option<String> {
+        self.0.as_ref().err().map(|error| format!("{error:?}"))
+    }
+}
+
+#[doc(hidden)]
+pub trait ErrorOpaque {
+    fn error(&self) -> Option<String>;
+}
+
+impl<T, E> ErrorOpaque for &CapturedError<'_, Result<T, E>> {
+    fn error(&self) -> Option<String> {
+        self.0.is_err().then(|| String::from("<?>"))
+    }
+}
+
+///other `Try` types, e.g. `ControlFlow`, are not inspected
+#[doc(hidden)]
+pub trait ErrorFallback {
+    fn error(&self) -> Option<String>;
+}
+
+impl<T> ErrorFallback for CapturedError<'_, T> {
+    fn error(&self) -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,40 @@
+//! The CSV rows of the exporters of the snapshot and history contexts, as RFC 4180 reads them.
+use std::io::Write;
+
+///a field quoted when it holds a separator, a quote or a line break, its quotes doubled
+fn field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+///writes the fields as one CRLF-terminated row
+pub(crate) fn write_row<I>(out: &mut impl Write, fields: I) -> std::io::Result<()>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let row = fields
+        .into_iter()
+        .map(|value| field(value.as_ref()).into_owned())
+        .collect::<Vec<_>>()
+        .join(",");
+    write!(out, "{row}\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_quoted_when_needed() {
+        let mut out = Vec::new();
+        write_row(&mut out, ["plain", "a,b", "say \"hi\"", "two\nlines", "", "cr\r"]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",,\"cr\r\"\r\n"
+        );
+    }
+}
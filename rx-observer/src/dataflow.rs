@@ -0,0 +1,291 @@
+//! A dataflow graph of the observed variables, exported as Graphviz DOT or Mermaid.
+//!
+//! [`DataflowContext`] collects the proposals made under `provenance = true`: every proposal adds an edge
+//! from each of its sources to the proposed variable, labelled with the expression text. Only the computations
+//! that actually ran are drawn, one cluster per decorated function:
+//! ```text
+//! digraph dataflow {
+//!   subgraph "cluster_quote" {
+//!     label="quote";
+//!     "quote/net" [label="net"];
+//!     "quote/price" [label="price", shape=box];
+//!     "quote/price" -> "quote/net" [label="price * quantity"];
+//!     ...
+//! ```
+//! Inputs, i.e. sources without a proposal in the call, are drawn as boxes. Each function is drawn on its own:
+//! the arguments of a decorated callee are inputs of its cluster, and the value it returns is not linked to
+//! the caller's variable it is assigned to.
+use crate::{ObserverContext, Provenance};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Debug, Display, Write};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge {
+    pub fn_name: String,
+    pub source: String,
+    pub target: String,
+    pub expression: String,
+}
+
+#[derive(Default)]
+struct Graph {
+    edges: BTreeSet<Edge>,
+    ///proposed variables per function, the other nodes are inputs
+    proposed: BTreeSet<(String, String)>,
+}
+
+///a context collecting the dataflow between the observed variables of each decorated function
+#[derive(Default)]
+pub struct DataflowContext {
+    graph: Mutex<Graph>,
+}
+
+impl DataflowContext {
+    pub const fn new() -> Self {
+        DataflowContext {
+            graph: Mutex::new(Graph {
+                edges: BTreeSet::new(),
+                proposed: BTreeSet::new(),
+            }),
+        }
+    }
+
+    ///the collected edges, ordered by function, source and target
+    pub fn edges(&self) -> Vec<Edge> {
+        self.graph
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .edges
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    ///the variables of each function, with whether they are inputs
+    fn nodes(graph: &Graph) -> BTreeMap<&str, BTreeMap<&str, bool>> {
+        let mut nodes: BTreeMap<&str, BTreeMap<&str, bool>> = BTreeMap::new();
+        for edge in &graph.edges {
+            for ident_name in [&edge.source, &edge.target] {
+                let is_input = !graph.proposed.contains(&(edge.fn_name.clone(), ident_name.clone()));
+                nodes
+                    .entry(edge.fn_name.as_str())
+                    .or_default()
+                    .insert(ident_name.as_str(), is_input);
+            }
+        }
+        nodes
+    }
+
+    ///the graph in the Graphviz DOT format
+    pub fn to_dot(&self) -> String {
+        let graph = self.graph.lock().unwrap_or_else(|e| e.into_inner());
+        let mut dot = String::from("digraph dataflow {\n  rankdir=LR;\n");
+        for (fn_name, idents) in Self::nodes(&graph) {
+            let _ = writeln!(dot, "  subgraph \"cluster_{}\" {{", dot_escape(fn_name));
+            let _ = writeln!(dot, "    label=\"{}\";", dot_escape(fn_name));
+            for (ident_name, is_input) in idents {
+                let shape = if is_input { ", shape=box" } else { "" };
+                let _ = writeln!(
+                    dot,
+                    "    \"{}/{}\" [label=\"{}\"{shape}];",
+                    dot_escape(fn_name),
+                    dot_escape(ident_name),
+                    dot_escape(ident_name)
+                );
+            }
+            for edge in graph.edges.iter().filter(|edge| edge.fn_name == fn_name) {
+                let _ = writeln!(
+                    dot,
+                    "    \"{fn_name}/{}\" -> \"{fn_name}/{}\" [label=\"{}\"];",
+                    dot_escape(&edge.source),
+                    dot_escape(&edge.target),
+                    dot_escape(&edge.expression),
+                    fn_name = dot_escape(fn_name)
+                );
+            }
+            dot.push_str("  }\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    ///the graph as a Mermaid flowchart
+    pub fn to_mermaid(&self) -> String {
+        let graph = self.graph.lock().unwrap_or_else(|e| e.into_inner());
+        let mut mermaid = String::from("flowchart LR\n");
+        for (fn_name, idents) in Self::nodes(&graph) {
+            let _ = writeln!(mermaid, "  subgraph {}[\"{}\"]", mermaid_id(fn_name, ""), mermaid_escape(fn_name));
+            for (ident_name, is_input) in idents {
+                let (open, close) = if is_input { ("[", "]") } else { ("(", ")") };
+                let _ = writeln!(
+                    mermaid,
+                    "    {}{open}\"{}\"{close}",
+                    mermaid_id(fn_name, ident_name),
+                    mermaid_escape(ident_name)
+                );
+            }
+            for edge in graph.edges.iter().filter(|edge| edge.fn_name == fn_name) {
+                let _ = writeln!(
+                    mermaid,
+                    "    {} -->|\"{}\"| {}",
+                    mermaid_id(fn_name, &edge.source),
+                    mermaid_escape(&edge.expression),
+                    mermaid_id(fn_name, &edge.target)
+                );
+            }
+            mermaid.push_str("  end\n");
+        }
+        mermaid
+    }
+
+    ///clears the collected graph
+    pub fn clear(&self) {
+        let mut graph = self.graph.lock().unwrap_or_else(|e| e.into_inner());
+        graph.edges.clear();
+        graph.proposed.clear();
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+///node ids are restricted to word characters
+fn mermaid_id(fn_name: &str, ident_name: &str) -> String {
+    format!("{fn_name}__{ident_name}")
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '_' { c } else { '_' })
+        .collect()
+}
+
+impl<'a> ObserverContext<'a> for DataflowContext {
+    fn register<T>(&self, identifier: T, _fn_name: &'a str, _ident_name: &'a str, _ident_type: &'a str) -> T
+    where
+        T: Display,
+    {
+        identifier
+    }
+
+    fn propose<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.graph
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .proposed
+            .insert((fn_name.to_string(), ident_name.to_string()));
+        identifier
+    }
+
+    fn propose_sourced<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, provenance: &Provenance<'a, '_>) -> T
+    where
+        T: Display,
+    {
+        let mut graph = self.graph.lock().unwrap_or_else(|e| e.into_inner());
+        graph
+            .proposed
+            .insert((fn_name.to_string(), ident_name.to_string()));
+        for (source, _) in provenance.sources {
+            graph.edges.insert(Edge {
+                fn_name: fn_name.to_string(),
+                source: source.to_string(),
+                target: ident_name.to_string(),
+                expression: provenance.expression.to_string(),
+            });
+        }
+        identifier
+    }
+
+    fn request<T>(&self, identifier: T, _fn_name: &str, _ident_name: &str) -> T
+    where
+        T: Display + FromStr + Clone,
+        <T as FromStr>::Err: Debug,
+    {
+        identifier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn propose(context: &DataflowContext, fn_name: &'static str, ident_name: &'static str, expression: &'static str, sources: &[(&'static str, Option<u64>)]) {
+        let provenance = Provenance {
+            sequence: 0,
+            expression,
+            sources,
+        };
+        context.propose_sourced(0, fn_name, ident_name, &provenance);
+    }
+
+    ///`net` computed from two inputs, a label quoting its expression, and a method whose name is no mermaid id
+    fn quoted() -> DataflowContext {
+        let context = DataflowContext::new();
+        propose(&context, "quote", "net", "price * quantity", &[("price", None), ("quantity", None)]);
+        propose(&context, "quote", "label", "format!(\"{net}\\n\")", &[("net", Some(1))]);
+        propose(&context, "Till::total", "total", "float + price", &[("price", None)]);
+        context
+    }
+
+    #[test]
+    fn dot_draws_a_cluster_per_function() {
+        let dot = r#"digraph dataflow {
+  rankdir=LR;
+  subgraph "cluster_Till::total" {
+    label="Till::total";
+    "Till::total/price" [label="price", shape=box];
+    "Till::total/total" [label="total"];
+    "Till::total/price" -> "Till::total/total" [label="float + price"];
+  }
+  subgraph "cluster_quote" {
+    label="quote";
+    "quote/label" [label="label"];
+    "quote/net" [label="net"];
+    "quote/price" [label="price", shape=box];
+    "quote/quantity" [label="quantity", shape=box];
+    "quote/net" -> "quote/label" [label="format!(\"{net}\\n\")"];
+    "quote/price" -> "quote/net" [label="price * quantity"];
+    "quote/quantity" -> "quote/net" [label="price * quantity"];
+  }
+}
+"#;
+        assert_eq!(quoted().to_dot(), dot);
+    }
+
+    #[test]
+    fn mermaid_draws_a_subgraph_per_function() {
+        let mermaid = r#"flowchart LR
+  subgraph Till__total__["Till::total"]
+    Till__total__price["price"]
+    Till__total__total("total")
+    Till__total__price -->|"float + price"| Till__total__total
+  end
+  subgraph quote__["quote"]
+    quote__label("label")
+    quote__net("net")
+    quote__price["price"]
+    quote__quantity["quantity"]
+    quote__net -->|"format!(#quot;{net}\n#quot;)"| quote__label
+    quote__price -->|"price * quantity"| quote__net
+    quote__quantity -->|"price * quantity"| quote__net
+  end
+"#;
+        assert_eq!(quoted().to_mermaid(), mermaid);
+    }
+
+    #[test]
+    fn clear_forgets_the_graph() {
+        let context = quoted();
+        assert_eq!(context.edges().len(), 4);
+        context.clear();
+        assert!(context.edges().is_empty());
+        assert_eq!(context.to_mermaid(), "flowchart LR\n");
+    }
+}
@@ -0,0 +1,247 @@
+//! A context computing requested variables from excel-like formulas (feature `formulas`).
+//!
+//! [`FormulasContext`] holds a sheet of formulas by variable name, e.g. `("a", "=(b+c)*s")`, `("s", "=SUM({b, c, 1})")`,
+//! and the latest registered and proposed values. A request of a variable with a formula gets the result of the formula,
+//! whose references are resolved to other formulas first, then to the stored values. The sheet is shared by all
+//! decorated functions, so formulas and stored values are named by the identifier alone. Numbers, booleans and text
+//! are passed to the formulas as such; a missing reference, a reference cycle or a result not parsing as the type of the
+//! requested variable leaves the requested value unchanged.
+//!
+//! A request evaluates the formulas on a copy of the sheet, so a long evaluation never holds up the threads observing
+//! through the same context.
+use crate::ObserverContext;
+use chrono::{DateTime, Local};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use xlformula_engine::types::{Boolean, Error, Value};
+use xlformula_engine::{NoCustomFunction, calculate, parse_formula};
+
+///the latest value of a variable
+#[derive(Clone, Debug)]
+pub struct Cell {
+    pub fn_name: String,
+    pub ident_name: String,
+    pub timestamp: DateTime<Local>,
+    pub operation: &'static str,
+    pub ident_value: String,
+    pub type_name: String,
+}
+
+impl Cell {
+    ///the value as passed to the formulas
+    fn value(&self) -> Value {
+        let type_name = self.type_name.trim_start_matches('&');
+        let type_name = type_name.rsplit("::").next().unwrap_or(type_name);
+        match type_name {
+            "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64" | "u128" | "usize"
+            | "f32" | "f64" => self
+                .ident_value
+                .parse()
+                .map(Value::Number)
+                .unwrap_or(Value::Error(Error::Value)),
+            "bool" => match self.ident_value.as_str() {
+                "true" => Value::Boolean(Boolean::True),
+                _ => Value::Boolean(Boolean::False),
+            },
+            _ => Value::Text(self.ident_value.clone()),
+        }
+    }
+}
+
+impl Display for Cell {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "‹{}:{}›: {}|{}| = {}({})",
+            self.fn_name, self.ident_name, self.timestamp, self.operation, self.ident_value, self.type_name
+        )
+    }
+}
+
+///context storing sheet with formulas
+///and calculating them for `request` delegate
+#[derive(Debug, Default)]
+pub struct FormulasContext {
+    vars: RwLock<HashMap<String, Cell>>,
+    formulas: RwLock<HashMap<String, String>>,
+}
+
+impl FormulasContext {
+    pub fn new<T>(formulas: Vec<(T, T)>) -> Self
+    where
+        T: AsRef<str>,
+    {
+        FormulasContext {
+            vars: RwLock::new(HashMap::new()),
+            formulas: RwLock::new(
+                formulas
+                    .iter()
+                    .map(|(name, formula)| (name.as_ref().to_owned(), formula.as_ref().to_owned()))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn vars(&self) -> RwLockReadGuard<'_, HashMap<String, Cell>> {
+        self.vars.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn vars_mut(&self) -> RwLockWriteGuard<'_, HashMap<String, Cell>> {
+        self.vars.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn formulas_mut(&self) -> RwLockWriteGuard<'_, HashMap<String, String>> {
+        self.formulas.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    ///a copy of the sheet
+    pub fn formulas(&self) -> HashMap<String, String> {
+        self.formulas.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    ///adds or replaces the formula of `ident_name`
+    pub fn set_formula(&self, ident_name: &str, formula: &str) {
+        self.formulas_mut().insert(ident_name.to_string(), formula.to_string());
+    }
+
+    pub fn remove_formula(&self, ident_name: &str) -> Option<String> {
+        self.formulas_mut().remove(ident_name)
+    }
+
+    ///the latest registered or proposed value of `ident_name`
+    pub fn get(&self, ident_name: &str) -> Option<Cell> {
+        self.vars().get(ident_name).cloned()
+    }
+
+    ///the result of the formula of `ident_name` as the formulas display it, `None` without a formula
+    pub fn evaluate(&self, ident_name: &str) -> Option<String> {
+        let formulas = self.formulas();
+        let vars = self.vars().clone();
+        let formula = formulas.get(ident_name)?;
+        let visiting = RefCell::new(vec![ident_name.to_string()]);
+        let result = Self::calculate(formula, &formulas, &vars, &visiting);
+        Some(calculate::result_to_string(result))
+    }
+
+    pub fn report_data(&self) -> Vec<String> {
+        self.vars().values().map(|cell| cell.to_string()).collect()
+    }
+
+    ///forgets the stored values, keeps the formulas
+    pub fn clear(&self) {
+        self.vars_mut().clear();
+    }
+
+    fn calculate(
+        formula: &str,
+        formulas: &HashMap<String, String>,
+        vars: &HashMap<String, Cell>,
+        visiting: &RefCell<Vec<String>>,
+    ) -> Value {
+        // TODO if we'd want custom functions, refer to xlformula_engine docs
+        let formula = parse_formula::parse_string_to_formula(formula, None::<NoCustomFunction>);
+        let data_function = |reference: String| Self::data_provider(reference, formulas, vars, visiting);
+        calculate::calculate_formula(formula, Some(&data_function))
+    }
+
+    ///formulas first, then the stored values
+    fn data_provider(
+        reference: String,
+        formulas: &HashMap<String, String>,
+        vars: &HashMap<String, Cell>,
+        visiting: &RefCell<Vec<String>>,
+    ) -> Value {
+        if let Some(formula) = formulas.get(&reference) {
+            if visiting.borrow().contains(&reference) {
+                return Value::Error(Error::Reference);
+            }
+            visiting.borrow_mut().push(reference);
+            let result = Self::calculate(formula, formulas, vars, visiting);
+            visiting.borrow_mut().pop();
+            return result;
+        }
+        match vars.get(&reference) {
+            Some(cell) => cell.value(),
+            None => Value::Error(Error::Reference),
+        }
+    }
+
+    fn insert(&self, fn_name: &str, ident_name: &str, operation: &'static str, value: String, type_name: &str) {
+        let cell = Cell {
+            fn_name: fn_name.to_string(),
+            ident_name: ident_name.to_string(),
+            timestamp: Local::now(),
+            operation,
+            ident_value: value,
+            type_name: type_name.to_string(),
+        };
+        self.vars_mut().insert(ident_name.to_string(), cell);
+    }
+}
+
+impl<'a> ObserverContext<'a> for FormulasContext {
+    fn register<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, ident_type: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.insert(fn_name, ident_name, "Registering", identifier.to_string(), ident_type);
+        identifier
+    }
+
+    fn propose<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str) -> T
+    where
+        T: Display,
+    {
+        let type_name = std::any::type_name_of_val(&identifier);
+        self.insert(fn_name, ident_name, "Proposing", identifier.to_string(), type_name);
+        identifier
+    }
+
+    fn request<T>(&self, identifier: T, _fn_name: &str, ident_name: &str) -> T
+    where
+        T: Display + FromStr + Clone,
+        <T as FromStr>::Err: Debug,
+    {
+        match self.evaluate(ident_name) {
+            Some(result) => result.parse::<T>().unwrap_or(identifier),
+            None => identifier,
+        }
+    }
+
+    fn scope_end(&self, _ident_name: &'a str, _fn_name: &'a str, _value: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formulas_refer_to_values_and_formulas() {
+        let context = FormulasContext::new(vec![("tax", "=net*0.2"), ("total", "=net+tax")]);
+        context.propose(150, "quote", "net");
+        assert_eq!(context.evaluate("tax").as_deref(), Some("30"));
+        assert_eq!(context.evaluate("total").as_deref(), Some("180"));
+        assert_eq!(context.request(0, "quote", "total"), 180);
+        assert_eq!(context.evaluate("net"), None);
+    }
+
+    #[test]
+    fn cycles_evaluate_to_a_reference_error() {
+        let context = FormulasContext::new(vec![("a", "=b+1"), ("b", "=a*2"), ("c", "=c+1")]);
+        let reference_error = calculate::result_to_string(Value::Error(Error::Reference));
+        assert_eq!(context.evaluate("a"), Some(reference_error.clone()));
+        assert_eq!(context.evaluate("c"), Some(reference_error));
+        // an unparsable result keeps the requested value
+        assert_eq!(context.request(7, "f", "a"), 7);
+    }
+
+    #[test]
+    fn a_formula_shared_by_two_references_is_no_cycle() {
+        let context = FormulasContext::new(vec![("double", "=base*2"), ("sum", "=double+double")]);
+        context.register(5, "f", "base", "i32");
+        assert_eq!(context.evaluate("sum").as_deref(), Some("20"));
+    }
+}
@@ -0,0 +1,337 @@
+//! A history for hot loops observed from many threads.
+//!
+//! Every observation into a [`HistoryContext`](super::HistoryContext) takes its write lock, so threads observing
+//! through the same context wait for each other. A [`BufferedHistoryContext`] instead stamps each record with a
+//! sequence number of the context (one atomic increment) and appends it to a buffer owned by the observing thread.
+//! It is not lock-free: the buffer sits behind a mutex only a flush contends for, so an append takes an uncontended
+//! lock, and the sequence counter is one cache line written by all the observing threads. Reading the history,
+//! or [`flush`], merges the buffers of all threads into one log ordered by sequence number, i.e. in the order
+//! the observations happened.
+//!
+//! A buffer is registered with the context the first time a thread observes through it and outlives the thread,
+//! so the records of finished threads are merged too. The thread refers to its buffers weakly: the buffers of a
+//! dropped context are freed with it, and the thread forgets them the next time it registers a buffer. `cargo bench --bench history_throughput --features history`
+//! compares both contexts across thread counts.
+//!
+//! [`flush`]: BufferedHistoryContext::flush
+use super::query::is_value;
+use super::{ChangeRecord, Query};
+use crate::{ObserverContext, Provenance};
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt::{Debug, Display};
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+///the records of one thread with their sequence numbers; only the owner thread appends,
+///so its lock is contended by a flush at most
+type Buffer = Mutex<Vec<(u64, ChangeRecord)>>;
+
+///the ids of the contexts, 0 while not assigned
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    ///the buffers of this thread by context id, a thread observes through a few live contexts at most;
+    ///the contexts own the buffers
+    static BUFFERS: RefCell<Vec<(u64, Weak<Buffer>)>> = const { RefCell::new(Vec::new()) };
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+///appends the records of the ordered runs to `merged` in the order of their sequence numbers
+fn merge_runs(runs: Vec<Vec<(u64, ChangeRecord)>>, merged: &mut Vec<(u64, ChangeRecord)>) {
+    merged.reserve(runs.iter().map(Vec::len).sum());
+    let mut runs = runs.into_iter().map(|run| run.into_iter().peekable()).collect::<Vec<_>>();
+    let mut heads = runs
+        .iter_mut()
+        .enumerate()
+        .filter_map(|(index, run)| Some(Reverse((run.peek()?.0, index))))
+        .collect::<BinaryHeap<_>>();
+    while let Some(Reverse((_, index))) = heads.pop() {
+        let run = &mut runs[index];
+        merged.extend(run.next());
+        if let Some((sequence, _)) = run.peek() {
+            heads.push(Reverse((*sequence, index)));
+        }
+    }
+}
+
+///a history context appending to per-thread buffers, merged in order on read
+#[derive(Debug, Default)]
+pub struct BufferedHistoryContext {
+    id: AtomicU64,
+    sequence: AtomicU64,
+    ///the buffers of all threads having observed through this context
+    buffers: Mutex<Vec<Arc<Buffer>>>,
+    ///the merged records, ordered by sequence number
+    merged: Mutex<Vec<(u64, ChangeRecord)>>,
+}
+
+impl BufferedHistoryContext {
+    pub const fn new() -> Self {
+        BufferedHistoryContext {
+            id: AtomicU64::new(0),
+            sequence: AtomicU64::new(0),
+            buffers: Mutex::new(Vec::new()),
+            merged: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn id(&self) -> u64 {
+        let id = self.id.load(Ordering::Relaxed);
+        if id != 0 {
+            return id;
+        }
+        let new_id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        match self.id.compare_exchange(0, new_id, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => new_id,
+            Err(id) => id,
+        }
+    }
+
+    fn register_buffer(&self) -> Arc<Buffer> {
+        let buffer = Arc::new(Buffer::default());
+        lock(&self.buffers).push(buffer.clone());
+        buffer
+    }
+
+    fn push(&self, change: ChangeRecord) {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        let id = self.id();
+        let mut change = Some(change);
+        let _ = BUFFERS.try_with(|buffers| {
+            let mut buffers = buffers.borrow_mut();
+            // the ids are never reused, the buffer found is the one of this context and alive
+            let buffer = buffers.iter().find(|(buffer_id, _)| *buffer_id == id);
+            let buffer = buffer.and_then(|(_, buffer)| buffer.upgrade()).unwrap_or_else(|| {
+                buffers.retain(|(_, buffer)| buffer.strong_count() > 0);
+                let buffer = self.register_buffer();
+                buffers.push((id, Arc::downgrade(&buffer)));
+                buffer
+            });
+            lock(&buffer).extend(change.take().map(|change| (sequence, change)));
+        });
+        // observed from a thread-local destructor
+        if let Some(change) = change {
+            lock(&self.register_buffer()).push((sequence, change));
+        }
+    }
+
+    ///moves the records of all thread buffers into the merged log
+    pub fn flush(&self) {
+        // every buffer is ordered already, its thread stamps its records in turn
+        let runs = lock(&self.buffers)
+            .iter()
+            .map(|buffer| std::mem::take(&mut *lock(buffer)))
+            .filter(|run| !run.is_empty())
+            .collect::<Vec<_>>();
+        let Some(first) = runs.iter().map(|run| run[0].0).min() else {
+            return;
+        };
+        let mut merged = lock(&self.merged);
+        // a record stamped before the previous flush may have been appended after it
+        let from = merged.partition_point(|(sequence, _)| *sequence < first);
+        let end = merged.len();
+        merge_runs(runs, &mut merged);
+        if from < end {
+            merged[from..].sort_by_key(|(sequence, _)| *sequence);
+        }
+    }
+
+    fn merged(&self) -> MutexGuard<'_, Vec<(u64, ChangeRecord)>> {
+        self.flush();
+        lock(&self.merged)
+    }
+
+    ///a copy of the records in the order of the observations
+    pub fn records(&self) -> Vec<ChangeRecord> {
+        self.merged().iter().map(|(_, change)| change.clone()).collect()
+    }
+
+    ///a copy of the records matching `query`, in the order of the observations
+    pub fn select(&self, query: &Query<'_>) -> Vec<ChangeRecord> {
+        self.merged()
+            .iter()
+            .map(|(_, change)| change)
+            .filter(|change| query.matches(change))
+            .cloned()
+            .collect()
+    }
+
+    ///the latest value registered, proposed or requested for the variable
+    pub fn last_value(&self, fn_name: &str, ident_name: &str) -> Option<String> {
+        self.merged()
+            .iter()
+            .rev()
+            .map(|(_, change)| change)
+            .find(|change| is_value(change) && change.fn_name == fn_name && change.ident_name == ident_name)
+            .map(|change| change.ident_value.clone())
+    }
+
+    ///the records displayed one per line
+    pub fn report_data(&self) -> Vec<String> {
+        self.merged().iter().map(|(_, change)| change.to_string()).collect()
+    }
+
+    ///writes the records as CSV, one row per record, see [`export`](super::export)
+    pub fn write_csv_long(&self, out: impl Write) -> std::io::Result<()> {
+        super::export::write_long(&self.records(), out)
+    }
+
+    ///writes the records as CSV, one row per invocation and one column per variable, see [`export`](super::export)
+    pub fn write_csv_wide(&self, out: impl Write) -> std::io::Result<()> {
+        super::export::write_wide(&self.records(), out)
+    }
+
+    ///returns the number of records in the history log
+    pub fn log_length(&self) -> usize {
+        self.merged().len()
+    }
+
+    ///clears the merged log and the buffers of all threads
+    pub fn clear(&self) {
+        for buffer in lock(&self.buffers).iter() {
+            lock(buffer).clear();
+        }
+        lock(&self.merged).clear();
+    }
+}
+
+impl<'a> ObserverContext<'a> for BufferedHistoryContext {
+    fn register<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, ident_type: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.push(ChangeRecord::register(&identifier, fn_name, ident_name, ident_type));
+        identifier
+    }
+
+    fn propose<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.push(ChangeRecord::propose(&identifier, fn_name, ident_name));
+        identifier
+    }
+
+    fn propose_sourced<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, provenance: &Provenance<'a, '_>) -> T
+    where
+        T: Display,
+    {
+        self.push(ChangeRecord::propose_sourced(&identifier, fn_name, ident_name, provenance));
+        identifier
+    }
+
+    fn request<T>(&self, identifier: T, fn_name: &str, ident_name: &str) -> T
+    where
+        T: Display + FromStr + Clone,
+        <T as FromStr>::Err: Debug,
+    {
+        self.push(ChangeRecord::request(&identifier, fn_name, ident_name));
+        identifier
+    }
+
+    fn check_failed(&self, condition: &'a str, fn_name: &'a str, values: &[(&'a str, String)]) {
+        self.push(ChangeRecord::check_failed(condition, fn_name, values));
+    }
+
+    fn trace(&self, expression: &'a str, fn_name: &'a str, value: &str) {
+        self.push(ChangeRecord::trace(expression, fn_name, value));
+    }
+
+    fn step(&self, statement: &'a str, fn_name: &'a str, line: u32) {
+        self.push(ChangeRecord::step(statement, fn_name, line));
+    }
+
+    fn decision(&self, condition: &'a str, fn_name: &'a str, branch: &'a str, values: &[(&'a str, String)]) {
+        self.push(ChangeRecord::decision(condition, fn_name, branch, values));
+    }
+
+    fn error(&self, expression: &'a str, fn_name: &'a str, error: &str) {
+        self.push(ChangeRecord::error(expression, fn_name, error));
+    }
+
+    fn scope_end(&self, ident_name: &'a str, fn_name: &'a str, value: &str) {
+        self.push(ChangeRecord::scope_end(ident_name, fn_name, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::OperationType;
+
+    fn run(sequences: &[u64]) -> Vec<(u64, ChangeRecord)> {
+        sequences
+            .iter()
+            .map(|sequence| {
+                let record = ChangeRecord::new(OperationType::Propose, "f", "x", sequence.to_string());
+                (*sequence, record)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merge_runs_interleaves_by_sequence() {
+        let mut merged = run(&[0]);
+        merge_runs(vec![run(&[2, 5, 6]), run(&[]), run(&[1, 3]), run(&[4, 7])], &mut merged);
+        let sequences = merged.iter().map(|(sequence, _)| *sequence).collect::<Vec<_>>();
+        assert_eq!(sequences, [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert!(merged.iter().all(|(sequence, record)| record.ident_value == sequence.to_string()));
+    }
+
+    #[test]
+    fn flush_orders_the_records_of_all_threads() {
+        let history = BufferedHistoryContext::new();
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let history = &history;
+                scope.spawn(move || {
+                    for value in 0..100 {
+                        history.propose(thread * 1000 + value, "worker", "x");
+                        if value % 30 == 0 {
+                            history.flush();
+                        }
+                    }
+                });
+            }
+        });
+        history.propose(-1, "main", "x");
+        let merged = history.merged();
+        assert_eq!(merged.len(), 401);
+        assert!(merged.iter().enumerate().all(|(index, (sequence, _))| *sequence == index as u64));
+        for thread in 0..4 {
+            let values = merged
+                .iter()
+                .filter(|(_, record)| record.fn_name == "worker")
+                .filter_map(|(_, record)| record.ident_value.parse::<i32>().ok())
+                .filter(|value| value / 1000 == thread)
+                .collect::<Vec<_>>();
+            assert!(values.windows(2).all(|pair| pair[0] < pair[1]), "thread {thread} out of order");
+        }
+        assert_eq!(merged.last().unwrap().1.fn_name, "main");
+    }
+
+    #[test]
+    fn dropped_contexts_are_forgotten_by_the_thread() {
+        std::thread::spawn(|| {
+            for value in 0..3 {
+                let history = BufferedHistoryContext::new();
+                history.propose(value, "f", "x");
+                assert_eq!(history.records().len(), 1);
+            }
+            let kept = BufferedHistoryContext::new();
+            kept.propose(3, "f", "x");
+            kept.propose(4, "f", "x");
+            BUFFERS.with(|buffers| assert_eq!(buffers.borrow().len(), 1));
+            assert_eq!(kept.records().len(), 2);
+        })
+        .join()
+        .unwrap();
+    }
+}
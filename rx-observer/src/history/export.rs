@@ -0,0 +1,222 @@
+//! CSV exports of a history for spreadsheets, in two shapes.
+//!
+//! The long format has one row per record, the fields of the record in columns:
+//! ```text
+//! timestamp,operation,fn_name,ident_name,ident_value,type_name,invocation,sequence,sources
+//! 2025-04-29 14:00:00.000000,registering,quote,net,400,i32,7,,
+//! 2025-04-29 14:00:00.000010,proposing,quote,total,432,,7,3,net#1;tax#2
+//! ```
+//! The wide format has one row per invocation of a function and one column per `fn/ident`, holding the latest
+//! value registered, proposed or requested for the variable in that invocation:
+//! ```text
+//! invocation,fn_name,started,quote/net,quote/total
+//! 7,quote,2025-04-29 14:00:00.000000,400,432
+//! 9,quote,2025-04-29 14:00:01.000000,250,270
+//! ```
+//! The invocations are told apart by the frames of the functions decorated with `frame = true`. The records of
+//! a function without frames have no invocation number: a run of consecutive records of the function makes a row
+//! until a variable of the row is set again: back-to-back calls get a row each, a variable set in a loop starts
+//! a new row at every iteration, and calls interleaved with other functions split into several rows.
+use super::ChangeRecord;
+use super::query::is_value;
+use crate::csv::write_row;
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::io::Write;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.6f";
+
+fn timestamp(timestamp: &DateTime<Local>) -> String {
+    timestamp.format(TIMESTAMP_FORMAT).to_string()
+}
+
+fn optional(number: Option<u64>) -> String {
+    number.map(|number| number.to_string()).unwrap_or_default()
+}
+
+///writes the records as CSV in the long format, one row per record
+pub fn write_long<'r>(records: impl IntoIterator<Item = &'r ChangeRecord>, mut out: impl Write) -> std::io::Result<()> {
+    write_row(
+        &mut out,
+        [
+            "timestamp",
+            "operation",
+            "fn_name",
+            "ident_name",
+            "ident_value",
+            "type_name",
+            "invocation",
+            "sequence",
+            "sources",
+        ],
+    )?;
+    for record in records {
+        let sources = record
+            .sources
+            .iter()
+            .map(|(name, sequence)| match sequence {
+                Some(sequence) => format!("{name}#{sequence}"),
+                None => name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(";");
+        write_row(
+            &mut out,
+            [
+                timestamp(&record.timestamp),
+                record.operation.to_string(),
+                record.fn_name.clone(),
+                record.ident_name.clone(),
+                record.ident_value.clone(),
+                record.type_name.clone().unwrap_or_default(),
+                optional(record.invocation),
+                optional(record.sequence),
+                sources,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+///a row of the wide format, the values indexed by column
+struct Row<'r> {
+    invocation: Option<u64>,
+    fn_name: &'r str,
+    started: &'r DateTime<Local>,
+    values: Vec<Option<&'r str>>,
+}
+
+///writes the records as CSV in the wide format, one row per invocation and one column per variable,
+///both in the order of their first record
+pub fn write_wide<'r>(records: impl IntoIterator<Item = &'r ChangeRecord>, mut out: impl Write) -> std::io::Result<()> {
+    let mut columns = Vec::<(&str, &str)>::new();
+    let mut rows = Vec::<Row<'_>>::new();
+    let mut invocations = HashMap::<u64, usize>::new();
+    // the row of the previous value, continued by the next value of the same function without frames
+    // unless the value sets a column of the row again, i.e. belongs to the next call
+    let mut previous = None::<usize>;
+    for record in records.into_iter().filter(|record| is_value(record)) {
+        let variable = (record.fn_name.as_str(), record.ident_name.as_str());
+        let column = match columns.iter().position(|column| *column == variable) {
+            Some(column) => column,
+            None => {
+                columns.push(variable);
+                columns.len() - 1
+            }
+        };
+        let continued = match record.invocation {
+            Some(invocation) => invocations.get(&invocation).copied(),
+            None => previous.filter(|row| {
+                let row = &rows[*row];
+                row.invocation.is_none()
+                    && row.fn_name == record.fn_name
+                    && row.values.get(column).is_none_or(Option::is_none)
+            }),
+        };
+        let row = continued.unwrap_or_else(|| {
+            rows.push(Row {
+                invocation: record.invocation,
+                fn_name: &record.fn_name,
+                started: &record.timestamp,
+                values: Vec::new(),
+            });
+            if let Some(invocation) = record.invocation {
+                invocations.insert(invocation, rows.len() - 1);
+            }
+            rows.len() - 1
+        });
+        let values = &mut rows[row].values;
+        if values.len() <= column {
+            values.resize(column + 1, None);
+        }
+        values[column] = Some(&record.ident_value);
+        previous = Some(row);
+    }
+
+    let header = ["invocation".to_string(), "fn_name".to_string(), "started".to_string()];
+    let header = header
+        .into_iter()
+        .chain(columns.iter().map(|(fn_name, ident_name)| format!("{fn_name}/{ident_name}")));
+    write_row(&mut out, header)?;
+    for row in &rows {
+        let values = (0..columns.len()).map(|column| row.values.get(column).copied().flatten().unwrap_or_default());
+        let fields = [optional(row.invocation), row.fn_name.to_string(), timestamp(row.started)];
+        write_row(&mut out, fields.into_iter().chain(values.map(str::to_string)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::OperationType;
+
+    fn record(fn_name: &str, ident_name: &str, value: &str, invocation: Option<u64>) -> ChangeRecord {
+        ChangeRecord {
+            invocation,
+            ..ChangeRecord::new(OperationType::Propose, fn_name, ident_name, value.to_string())
+        }
+    }
+
+    ///the rows without the `started` column
+    fn wide(records: &[ChangeRecord]) -> Vec<String> {
+        let mut out = Vec::new();
+        write_wide(records, &mut out).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|row| {
+                let mut fields = row.split(',').collect::<Vec<_>>();
+                fields.remove(2);
+                fields.join(",")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frames_group_their_invocations() {
+        let records = [
+            record("quote", "net", "400", Some(7)),
+            record("quote", "net", "250", Some(9)),
+            record("quote", "total", "432", Some(7)),
+            record("quote", "total", "270", Some(9)),
+        ];
+        assert_eq!(
+            wide(&records),
+            ["invocation,fn_name,quote/net,quote/total", "7,quote,400,432", "9,quote,250,270"]
+        );
+    }
+
+    #[test]
+    fn back_to_back_calls_without_frames_get_a_row_each() {
+        let records = [
+            record("quote", "net", "400", None),
+            record("quote", "total", "432", None),
+            record("quote", "net", "250", None),
+            record("quote", "total", "270", None),
+            record("ship", "fee", "5", None),
+            record("quote", "total", "100", None),
+        ];
+        assert_eq!(
+            wide(&records),
+            [
+                "invocation,fn_name,quote/net,quote/total,ship/fee",
+                ",quote,400,432,",
+                ",quote,250,270,",
+                ",ship,,,5",
+                ",quote,,100,",
+            ]
+        );
+    }
+
+    #[test]
+    fn long_rows_quote_their_fields() {
+        let mut sourced = record("quote", "label", "a, \"b\"", Some(3));
+        sourced.sources = vec![(String::from("net"), Some(1)), (String::from("rate"), None)];
+        let mut out = Vec::new();
+        write_long([&sourced], &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let row = out.split("\r\n").nth(1).unwrap();
+        assert!(row.ends_with(",proposing,quote,label,\"a, \"\"b\"\"\",,3,,net#1;rate"), "{row}");
+    }
+}
@@ -0,0 +1,163 @@
+//! The log behind a [`HistoryContext`](super::HistoryContext), unbounded or a ring buffer.
+use super::ChangeRecord;
+use std::collections::VecDeque;
+
+///what a bounded history does with a record logged while it is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    ///drops the oldest record, the history keeps the latest `capacity` records
+    DropOldest,
+    ///drops the incoming record, the history keeps the first `capacity` records
+    DropNewest,
+    ///keeps one of every `n` records logged while full, dropping the oldest one for it,
+    ///so the history stays spread over a long run; `0` and `1` behave like `DropOldest`
+    Sample(u32),
+}
+
+#[derive(Debug)]
+pub(super) struct ChangesLog {
+    pub(super) records: VecDeque<ChangeRecord>,
+    ///`None` for an unbounded log
+    capacity: Option<usize>,
+    policy: OverflowPolicy,
+    ///the number of records dropped from the front, i.e. the position of the first record since the start
+    pub(super) evicted: u64,
+    pub(super) recorded: u64,
+    pub(super) dropped: u64,
+    ///the records logged while full, counted for `Sample`
+    overflowed: u64,
+}
+
+impl ChangesLog {
+    pub(super) const fn new(capacity: Option<usize>, policy: OverflowPolicy) -> Self {
+        ChangesLog {
+            records: VecDeque::new(),
+            capacity,
+            policy,
+            evicted: 0,
+            recorded: 0,
+            dropped: 0,
+            overflowed: 0,
+        }
+    }
+
+    pub(super) fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    pub(super) fn policy(&self) -> OverflowPolicy {
+        self.policy
+    }
+
+    pub(super) fn push(&mut self, record: ChangeRecord) {
+        let Some(capacity) = self.capacity.filter(|capacity| self.records.len() >= *capacity) else {
+            self.records.push_back(record);
+            self.recorded += 1;
+            return;
+        };
+        let keep = match self.policy {
+            OverflowPolicy::DropOldest => true,
+            OverflowPolicy::DropNewest => false,
+            OverflowPolicy::Sample(n) => {
+                self.overflowed += 1;
+                self.overflowed.is_multiple_of(u64::from(n.max(1)))
+            }
+        };
+        self.dropped += 1;
+        if !keep || capacity == 0 {
+            return;
+        }
+        self.records.pop_front();
+        self.evicted += 1;
+        self.records.push_back(record);
+        self.recorded += 1;
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.evicted += self.records.len() as u64;
+        self.records.clear();
+        self.overflowed = 0;
+    }
+
+    ///the record at `position` since the start, `None` once dropped or not logged yet
+    pub(super) fn at(&self, position: u64) -> Option<&ChangeRecord> {
+        let index = position.checked_sub(self.evicted)?;
+        self.records.get(usize::try_from(index).ok()?)
+    }
+
+    ///the position since the start of the next record to be logged
+    pub(super) fn end(&self) -> u64 {
+        self.evicted + self.records.len() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::OperationType;
+
+    ///a log of the values `0..count` pushed in order
+    fn filled(capacity: Option<usize>, policy: OverflowPolicy, count: u32) -> ChangesLog {
+        let mut log = ChangesLog::new(capacity, policy);
+        for value in 0..count {
+            log.push(ChangeRecord::new(OperationType::Propose, "f", "x", value.to_string()));
+        }
+        log
+    }
+
+    fn values(log: &ChangesLog) -> Vec<&str> {
+        log.records.iter().map(|record| record.ident_value.as_str()).collect()
+    }
+
+    #[test]
+    fn unbounded_keeps_everything() {
+        let log = filled(None, OverflowPolicy::DropNewest, 5);
+        assert_eq!(values(&log), ["0", "1", "2", "3", "4"]);
+        assert_eq!((log.recorded, log.dropped, log.evicted), (5, 0, 0));
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_latest() {
+        let log = filled(Some(3), OverflowPolicy::DropOldest, 5);
+        assert_eq!(values(&log), ["2", "3", "4"]);
+        assert_eq!((log.recorded, log.dropped, log.evicted), (5, 2, 2));
+        assert!(log.at(1).is_none());
+        assert_eq!(log.at(2).map(|record| record.ident_value.as_str()), Some("2"));
+        assert_eq!(log.end(), 5);
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_first() {
+        let log = filled(Some(3), OverflowPolicy::DropNewest, 5);
+        assert_eq!(values(&log), ["0", "1", "2"]);
+        assert_eq!((log.recorded, log.dropped, log.evicted), (3, 2, 0));
+        assert_eq!(log.end(), 3);
+    }
+
+    #[test]
+    fn sample_keeps_one_in_n_overflowing_records() {
+        let log = filled(Some(2), OverflowPolicy::Sample(3), 8);
+        assert_eq!(values(&log), ["4", "7"]);
+        assert_eq!((log.recorded, log.dropped, log.evicted), (4, 6, 2));
+        for n in [0, 1] {
+            let log = filled(Some(3), OverflowPolicy::Sample(n), 5);
+            assert_eq!(values(&log), ["2", "3", "4"], "Sample({n})");
+        }
+    }
+
+    #[test]
+    fn zero_capacity_drops_everything() {
+        let log = filled(Some(0), OverflowPolicy::DropOldest, 2);
+        assert!(log.records.is_empty());
+        assert_eq!((log.recorded, log.dropped), (0, 2));
+    }
+
+    #[test]
+    fn clear_moves_the_positions_on() {
+        let mut log = filled(Some(2), OverflowPolicy::Sample(2), 3);
+        log.clear();
+        assert_eq!(log.end(), 2);
+        log.push(ChangeRecord::new(OperationType::Propose, "f", "x", String::from("a")));
+        assert_eq!(log.at(2).map(|record| record.ident_value.as_str()), Some("a"));
+    }
+}
@@ -0,0 +1,402 @@
+//! A context logging every observation (feature `history`).
+//!
+//! [`HistoryContext`] appends a [`ChangeRecord`] per delegate call: the values of the registered, proposed
+//! and requested variables, and the failed checks, traces, steps, decisions, errors and bindings going out of scope.
+//! Proposals under `provenance = true` keep their sequence number and sources, so [`HistoryContext::inputs_of`]
+//! can follow a value back to its inputs, and a [`Query`] selects records by function, identifier, operation,
+//! time range and value. [`HistoryContext::bounded`] keeps the history in a ring buffer for long-running services,
+//! dropping records by an [`OverflowPolicy`] once full and counting them, and [`export`] writes it as CSV
+//! for spreadsheets. A record displays as
+//! ```text
+//! 2025-04-29 14:00:00.000000 +03:00|proposing|‹quote/total›=432 #3 <- [net#1, tax#2]
+//! ```
+//!
+//! The log interleaves the observations of all threads in the order they were made, see [`BufferedHistoryContext`]
+//! for hot loops on many threads.
+use crate::{ObserverContext, Provenance};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::{Debug, Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+pub mod buffered;
+pub mod export;
+mod log;
+pub mod query;
+use log::ChangesLog;
+pub use buffered::BufferedHistoryContext;
+pub use log::OverflowPolicy;
+pub use query::{Cursor, Query, Records};
+
+///the delegate a [`ChangeRecord`] comes from
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OperationType {
+    Register,
+    Propose,
+    Request,
+    CheckFailed,
+    Trace,
+    Step,
+    Decision,
+    Error,
+    ScopeEnd,
+}
+
+impl Display for OperationType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            OperationType::Register => "registering",
+            OperationType::Propose => "proposing",
+            OperationType::Request => "requesting",
+            OperationType::CheckFailed => "check failed",
+            OperationType::Trace => "tracing",
+            OperationType::Step => "stepping",
+            OperationType::Decision => "deciding",
+            OperationType::Error => "returning error",
+            OperationType::ScopeEnd => "going out of scope",
+        })
+    }
+}
+
+///an observation; `ident_name` and `ident_value` are the condition and the values for a failed check,
+///the expression and its value for a trace or an error, `line N` and the statement for a step,
+///the condition and the branch taken with the values for a decision
+#[derive(Serialize, Clone, Debug)]
+pub struct ChangeRecord {
+    pub timestamp: DateTime<Local>,
+    pub operation: OperationType,
+    pub fn_name: String,
+    pub ident_name: String,
+    pub ident_value: String,
+    pub type_name: Option<String>,
+    ///the call of `fn_name` the record was observed in, for functions decorated with `frame = true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invocation: Option<u64>,
+    ///the sequence number of a proposal under `provenance = true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+    ///the observed identifiers the proposed value was computed from, with the sequence numbers of their proposals
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<(String, Option<u64>)>,
+}
+
+impl ChangeRecord {
+    fn new(operation: OperationType, fn_name: &str, ident_name: &str, ident_value: String) -> Self {
+        ChangeRecord {
+            timestamp: Local::now(),
+            operation,
+            fn_name: fn_name.to_string(),
+            ident_name: ident_name.to_string(),
+            ident_value,
+            type_name: None,
+            invocation: crate::recorder::invocation(fn_name),
+            sequence: None,
+            sources: Vec::new(),
+        }
+    }
+
+    // the records of the delegates, shared by the history contexts
+
+    fn register(identifier: &impl Display, fn_name: &str, ident_name: &str, ident_type: &str) -> Self {
+        ChangeRecord {
+            type_name: Some(ident_type.to_string()),
+            ..Self::new(OperationType::Register, fn_name, ident_name, identifier.to_string())
+        }
+    }
+
+    fn propose(identifier: &impl Display, fn_name: &str, ident_name: &str) -> Self {
+        Self::new(OperationType::Propose, fn_name, ident_name, identifier.to_string())
+    }
+
+    fn propose_sourced(identifier: &impl Display, fn_name: &str, ident_name: &str, provenance: &Provenance<'_, '_>) -> Self {
+        ChangeRecord {
+            sequence: Some(provenance.sequence),
+            sources: provenance
+                .sources
+                .iter()
+                .map(|(name, sequence)| (name.to_string(), *sequence))
+                .collect(),
+            ..Self::propose(identifier, fn_name, ident_name)
+        }
+    }
+
+    fn request<T: Display>(identifier: &T, fn_name: &str, ident_name: &str) -> Self {
+        ChangeRecord {
+            type_name: Some(std::any::type_name::<T>().to_string()),
+            ..Self::new(OperationType::Request, fn_name, ident_name, identifier.to_string())
+        }
+    }
+
+    fn check_failed(condition: &str, fn_name: &str, values: &[(&str, String)]) -> Self {
+        Self::new(OperationType::CheckFailed, fn_name, condition, joined(values))
+    }
+
+    fn trace(expression: &str, fn_name: &str, value: &str) -> Self {
+        Self::new(OperationType::Trace, fn_name, expression, value.to_string())
+    }
+
+    fn step(statement: &str, fn_name: &str, line: u32) -> Self {
+        Self::new(OperationType::Step, fn_name, &format!("line {line}"), statement.to_string())
+    }
+
+    fn decision(condition: &str, fn_name: &str, branch: &str, values: &[(&str, String)]) -> Self {
+        Self::new(
+            OperationType::Decision,
+            fn_name,
+            condition,
+            format!("{branch} ({})", joined(values)),
+        )
+    }
+
+    fn error(expression: &str, fn_name: &str, error: &str) -> Self {
+        Self::new(OperationType::Error, fn_name, expression, error.to_string())
+    }
+
+    fn scope_end(ident_name: &str, fn_name: &str, value: &str) -> Self {
+        Self::new(OperationType::ScopeEnd, fn_name, ident_name, value.to_string())
+    }
+}
+
+impl Display for ChangeRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            timestamp,
+            operation,
+            fn_name,
+            ident_name,
+            ident_value,
+            ..
+        } = &self;
+        write!(f, "{timestamp}|{operation}|‹{fn_name}/{ident_name}›")?;
+        if let Some(type_name) = &self.type_name {
+            write!(f, "({type_name})")?;
+        }
+        write!(f, "={ident_value}")?;
+        if let Some(sequence) = self.sequence {
+            let sources = self
+                .sources
+                .iter()
+                .map(|(name, source_sequence)| match source_sequence {
+                    Some(source_sequence) => format!("{name}#{source_sequence}"),
+                    None => name.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, " #{sequence} <- [{sources}]")?;
+        }
+        Ok(())
+    }
+}
+
+fn joined(values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .map(|(ident_name, ident_value)| format!("{ident_name}={ident_value}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+///a context logging every observation in order, without a bound or in a ring buffer of `capacity` records
+#[derive(Debug)]
+pub struct HistoryContext {
+    changes_log: RwLock<ChangesLog>,
+}
+
+impl HistoryContext {
+    pub const fn new() -> Self {
+        HistoryContext {
+            changes_log: RwLock::new(ChangesLog::new(None, OverflowPolicy::DropOldest)),
+        }
+    }
+
+    ///a history keeping at most `capacity` records, `policy` choosing the ones dropped once full
+    pub const fn bounded(capacity: usize, policy: OverflowPolicy) -> Self {
+        HistoryContext {
+            changes_log: RwLock::new(ChangesLog::new(Some(capacity), policy)),
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, ChangesLog> {
+        self.changes_log.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, ChangesLog> {
+        self.changes_log.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, change: ChangeRecord) {
+        self.write().push(change);
+    }
+
+    ///`None` for an unbounded history
+    pub fn capacity(&self) -> Option<usize> {
+        self.read().capacity()
+    }
+
+    pub fn policy(&self) -> OverflowPolicy {
+        self.read().policy()
+    }
+
+    ///the number of records accepted since the start, including the ones dropped later to make room
+    pub fn recorded(&self) -> u64 {
+        self.read().recorded
+    }
+
+    ///the number of records dropped since the start because the history was full
+    pub fn dropped(&self) -> u64 {
+        self.read().dropped
+    }
+
+    ///a copy of the records, oldest first
+    pub fn records(&self) -> Vec<ChangeRecord> {
+        self.read().records.iter().cloned().collect()
+    }
+
+    ///the records borrowed in place, see [`Records`]
+    pub fn view(&self) -> Records<'_> {
+        Records { log: self.read() }
+    }
+
+    ///a copy of the records matching `query`, oldest first
+    pub fn select(&self, query: &Query<'_>) -> Vec<ChangeRecord> {
+        self.read()
+            .records
+            .iter()
+            .filter(|record| query.matches(record))
+            .cloned()
+            .collect()
+    }
+
+    ///the latest value registered, proposed or requested for the variable
+    pub fn last_value(&self, fn_name: &str, ident_name: &str) -> Option<String> {
+        self.view()
+            .last(fn_name, ident_name)
+            .map(|record| record.ident_value.clone())
+    }
+
+    ///a cursor over the records matching `query`, from the oldest one
+    pub fn cursor<'h>(&'h self, query: Query<'h>) -> Cursor<'h> {
+        Cursor::new(self, query)
+    }
+
+    ///the records displayed one per line
+    pub fn report_data(&self) -> Vec<String> {
+        self.read().records.iter().map(|change| change.to_string()).collect()
+    }
+
+    ///the records serialized as JSON objects
+    pub fn report_as_json(&self) -> Vec<String> {
+        self.read().records.iter().flat_map(serde_json::to_string).collect()
+    }
+
+    ///writes a copy of the records as CSV, one row per record, see [`export`]
+    pub fn write_csv_long(&self, out: impl Write) -> std::io::Result<()> {
+        export::write_long(&self.records(), out)
+    }
+
+    ///writes a copy of the records as CSV, one row per invocation and one column per variable, see [`export`]
+    pub fn write_csv_wide(&self, out: impl Write) -> std::io::Result<()> {
+        export::write_wide(&self.records(), out)
+    }
+
+    ///returns the number of records in the history log
+    pub fn log_length(&self) -> usize {
+        self.read().records.len()
+    }
+
+    ///the inputs a proposed variable was computed from, following its latest proposal in `fn_name` back
+    ///through the proposals of its sources; the observed identifiers without a proposal are the inputs
+    pub fn inputs_of(&self, fn_name: &str, ident_name: &str) -> BTreeSet<String> {
+        let log = &self.read().records;
+        let mut inputs = BTreeSet::new();
+        let mut pending = log
+            .iter()
+            .rev()
+            .find(|change| change.fn_name == fn_name && change.ident_name == ident_name && change.sequence.is_some())
+            .map(|change| change.sources.clone())
+            .unwrap_or_default();
+        while let Some((name, sequence)) = pending.pop() {
+            let proposal = sequence.and_then(|sequence| log.iter().find(|change| change.sequence == Some(sequence)));
+            match proposal {
+                Some(proposal) if !proposal.sources.is_empty() => pending.extend(proposal.sources.iter().cloned()),
+                Some(_) | None => {
+                    inputs.insert(name);
+                }
+            }
+        }
+        inputs
+    }
+
+    ///clears the context history log, the counters go on
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+}
+
+impl Default for HistoryContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> ObserverContext<'a> for HistoryContext {
+    fn register<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, ident_type: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.push(ChangeRecord::register(&identifier, fn_name, ident_name, ident_type));
+        identifier
+    }
+
+    fn propose<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.push(ChangeRecord::propose(&identifier, fn_name, ident_name));
+        identifier
+    }
+
+    fn propose_sourced<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, provenance: &Provenance<'a, '_>) -> T
+    where
+        T: Display,
+    {
+        self.push(ChangeRecord::propose_sourced(&identifier, fn_name, ident_name, provenance));
+        identifier
+    }
+
+    fn request<T>(&self, identifier: T, fn_name: &str, ident_name: &str) -> T
+    where
+        T: Display + FromStr + Clone,
+        <T as FromStr>::Err: Debug,
+    {
+        self.push(ChangeRecord::request(&identifier, fn_name, ident_name));
+        identifier
+    }
+
+    fn check_failed(&self, condition: &'a str, fn_name: &'a str, values: &[(&'a str, String)]) {
+        self.push(ChangeRecord::check_failed(condition, fn_name, values));
+    }
+
+    fn trace(&self, expression: &'a str, fn_name: &'a str, value: &str) {
+        self.push(ChangeRecord::trace(expression, fn_name, value));
+    }
+
+    fn step(&self, statement: &'a str, fn_name: &'a str, line: u32) {
+        self.push(ChangeRecord::step(statement, fn_name, line));
+    }
+
+    fn decision(&self, condition: &'a str, fn_name: &'a str, branch: &'a str, values: &[(&'a str, String)]) {
+        self.push(ChangeRecord::decision(condition, fn_name, branch, values));
+    }
+
+    fn error(&self, expression: &'a str, fn_name: &'a str, error: &str) {
+        self.push(ChangeRecord::error(expression, fn_name, error));
+    }
+
+    fn scope_end(&self, ident_name: &'a str, fn_name: &'a str, value: &str) {
+        self.push(ChangeRecord::scope_end(ident_name, fn_name, value));
+    }
+}
@@ -0,0 +1,280 @@
+//! Selecting records of a history.
+//!
+//! A [`Query`] is built from optional criteria, all of which a record must meet:
+//! ```text
+//! let proposals = HISTORYCONTEXT.select(
+//!     &Query::new()
+//!         .fn_name("quote")
+//!         .operation(OperationType::Propose)
+//!         .value(|value| value.parse::<i32>().is_ok_and(|total| total > 100)),
+//! );
+//! ```
+//! [`HistoryContext::view`] borrows the records in place for as long as the view lives,
+//! a [`Cursor`] walks the history one record at a time without holding a lock between records,
+//! so it keeps up with the records logged meanwhile.
+use super::{ChangeRecord, ChangesLog, HistoryContext, OperationType};
+use chrono::{DateTime, Local};
+use std::collections::vec_deque::Iter;
+use std::sync::RwLockReadGuard;
+
+type ValuePredicate<'q> = Box<dyn Fn(&str) -> bool + 'q>;
+
+///criteria selecting [`ChangeRecord`]s, a record matches when it meets all of them
+#[derive(Default)]
+pub struct Query<'q> {
+    fn_name: Option<String>,
+    ident_name: Option<String>,
+    operations: Vec<OperationType>,
+    since: Option<DateTime<Local>>,
+    until: Option<DateTime<Local>>,
+    value: Option<ValuePredicate<'q>>,
+}
+
+impl<'q> Query<'q> {
+    ///a query matching every record
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fn_name(mut self, fn_name: &str) -> Self {
+        self.fn_name = Some(fn_name.to_string());
+        self
+    }
+
+    ///the identifier, or the expression, condition or `line N` of the records of other operations
+    pub fn ident_name(mut self, ident_name: &str) -> Self {
+        self.ident_name = Some(ident_name.to_string());
+        self
+    }
+
+    ///records of `operation`, or of any of the operations given by repeated calls
+    pub fn operation(mut self, operation: OperationType) -> Self {
+        self.operations.push(operation);
+        self
+    }
+
+    ///records logged at `since` or later
+    pub fn since(mut self, since: DateTime<Local>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    ///records logged before `until`
+    pub fn until(mut self, until: DateTime<Local>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    ///records whose value meets `predicate`
+    pub fn value(mut self, predicate: impl Fn(&str) -> bool + 'q) -> Self {
+        self.value = Some(Box::new(predicate));
+        self
+    }
+
+    pub fn matches(&self, record: &ChangeRecord) -> bool {
+        self.fn_name.as_ref().is_none_or(|fn_name| *fn_name == record.fn_name)
+            && self
+                .ident_name
+                .as_ref()
+                .is_none_or(|ident_name| *ident_name == record.ident_name)
+            && (self.operations.is_empty() || self.operations.contains(&record.operation))
+            && self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp < until)
+            && self.value.as_ref().is_none_or(|predicate| predicate(&record.ident_value))
+    }
+
+    ///the matching records of `records`, in their order
+    pub fn filter<'r>(
+        &'r self,
+        records: impl IntoIterator<Item = &'r ChangeRecord>,
+    ) -> impl Iterator<Item = &'r ChangeRecord> {
+        records.into_iter().filter(move |record| self.matches(record))
+    }
+}
+
+///whether the record carries a value of the variable, i.e. is a register, propose or request
+pub(super) fn is_value(record: &ChangeRecord) -> bool {
+    matches!(
+        record.operation,
+        OperationType::Register | OperationType::Propose | OperationType::Request
+    )
+}
+
+///the records of a [`HistoryContext`] borrowed in place, oldest first;
+///the observers logging meanwhile wait until the view is dropped
+pub struct Records<'h> {
+    pub(super) log: RwLockReadGuard<'h, ChangesLog>,
+}
+
+impl Records<'_> {
+    pub fn iter(&self) -> Iter<'_, ChangeRecord> {
+        self.log.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.log.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.log.records.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ChangeRecord> {
+        self.log.records.get(index)
+    }
+
+    ///the records matching `query`, oldest first
+    pub fn select<'r>(&'r self, query: &'r Query<'_>) -> impl DoubleEndedIterator<Item = &'r ChangeRecord> {
+        self.iter().filter(move |record| query.matches(record))
+    }
+
+    ///the latest record carrying a value of the variable
+    pub fn last(&self, fn_name: &str, ident_name: &str) -> Option<&ChangeRecord> {
+        self.iter()
+            .rev()
+            .find(|record| is_value(record) && record.fn_name == fn_name && record.ident_name == ident_name)
+    }
+}
+
+impl<'r> IntoIterator for &'r Records<'_> {
+    type Item = &'r ChangeRecord;
+    type IntoIter = Iter<'r, ChangeRecord>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+///walks the records of a [`HistoryContext`] matching a query, taking the lock for one step at a time;
+///the records logged after the cursor was created are reached too,
+///the ones cleared or dropped by a bounded history before the cursor reaches them are skipped
+pub struct Cursor<'h> {
+    history: &'h HistoryContext,
+    query: Query<'h>,
+    position: u64,
+}
+
+impl<'h> Cursor<'h> {
+    pub(super) fn new(history: &'h HistoryContext, query: Query<'h>) -> Self {
+        Cursor {
+            history,
+            query,
+            position: 0,
+        }
+    }
+
+    ///the position of the next record to look at, counted from the first record ever logged
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    ///moves to the records logged from now on
+    pub fn skip_to_end(&mut self) {
+        self.position = self.history.read().end();
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = ChangeRecord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let log = self.history.read();
+        self.position = self.position.max(log.evicted);
+        while let Some(record) = log.at(self.position) {
+            self.position += 1;
+            if self.query.matches(record) {
+                return Some(record.clone());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ObserverContext;
+    use crate::history::OverflowPolicy;
+
+    fn idents<'r>(records: impl IntoIterator<Item = &'r ChangeRecord>) -> Vec<&'r str> {
+        records.into_iter().map(|record| record.ident_name.as_str()).collect()
+    }
+
+    fn quote_history() -> HistoryContext {
+        let history = HistoryContext::new();
+        history.register(120, "quote", "price", "u32");
+        history.propose(360, "quote", "net");
+        history.propose(432, "quote", "total");
+        history.propose(5, "ship", "total");
+        history.request(432, "ship", "total");
+        history
+    }
+
+    #[test]
+    fn criteria_combine() {
+        let history = quote_history();
+        let view = history.view();
+        assert_eq!(idents(view.select(&Query::new().fn_name("quote"))), ["price", "net", "total"]);
+        assert_eq!(view.select(&Query::new().ident_name("total")).count(), 3);
+        let proposals = Query::new().operation(OperationType::Propose).fn_name("quote");
+        assert_eq!(idents(view.select(&proposals)), ["net", "total"]);
+        let values = Query::new()
+            .operation(OperationType::Register)
+            .operation(OperationType::Request)
+            .value(|value| value.parse::<u32>().is_ok_and(|value| value > 200));
+        assert_eq!(
+            view.select(&values).map(|record| &record.fn_name).collect::<Vec<_>>(),
+            ["ship"]
+        );
+        assert_eq!(view.last("ship", "total").unwrap().ident_value, "432");
+        assert_eq!(view.select(&Query::new()).count(), view.len());
+    }
+
+    #[test]
+    fn since_is_inclusive_and_until_exclusive() {
+        let history = quote_history();
+        let records = history.records();
+        let middle = records[2].timestamp;
+        let since = history.select(&Query::new().since(middle));
+        let until = history.select(&Query::new().until(middle));
+        assert_eq!(since.len(), records.iter().filter(|record| record.timestamp >= middle).count());
+        assert_eq!(since.len() + until.len(), records.len());
+        assert!(until.iter().all(|record| record.timestamp < middle));
+    }
+
+    #[test]
+    fn cursor_reaches_records_logged_later() {
+        let history = HistoryContext::new();
+        history.propose(1, "f", "a");
+        let mut cursor = history.cursor(Query::new().ident_name("a"));
+        assert_eq!(cursor.next().map(|record| record.ident_value), Some(String::from("1")));
+        assert!(cursor.next().is_none());
+        history.propose(2, "f", "b");
+        history.propose(3, "f", "a");
+        assert_eq!(cursor.next().map(|record| record.ident_value), Some(String::from("3")));
+        assert_eq!(cursor.position(), 3);
+        cursor.skip_to_end();
+        history.propose(4, "f", "a");
+        assert_eq!(cursor.map(|record| record.ident_value).collect::<Vec<_>>(), ["4"]);
+    }
+
+    #[test]
+    fn cursor_resumes_after_ring_eviction() {
+        let history = HistoryContext::bounded(3, OverflowPolicy::DropOldest);
+        for value in 0..2 {
+            history.propose(value, "f", "a");
+        }
+        let mut cursor = history.cursor(Query::new());
+        assert_eq!(cursor.next().map(|record| record.ident_value), Some(String::from("0")));
+        // 1 to 3 are evicted before the cursor reaches them
+        for value in 2..7 {
+            history.propose(value, "f", "a");
+        }
+        let rest = cursor.by_ref().map(|record| record.ident_value).collect::<Vec<_>>();
+        assert_eq!(rest, ["4", "5", "6"]);
+        assert_eq!(cursor.position(), 7);
+        history.clear();
+        history.propose(7, "f", "a");
+        assert_eq!(cursor.next().map(|record| record.ident_value), Some(String::from("7")));
+    }
+}
@@ -1,9 +1,33 @@
+pub mod assertion;
+pub mod binding;
+pub mod callsite;
+pub mod capture;
+#[cfg(any(feature = "snapshot", feature = "history"))]
+mod csv;
+pub mod dataflow;
+#[cfg(feature = "formulas")]
+pub mod formulas;
+#[cfg(feature = "history")]
+pub mod history;
 pub mod prelude;
+pub mod provenance;
+pub mod recorder;
+pub mod registry;
+pub mod scope;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+pub mod writer;
+pub use provenance::Provenance;
 use std::fmt::{Debug, Display};
 use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-// Default implementations print to the standard error
+#[doc(hidden)]
+pub mod __private {
+    pub use inventory;
+}
+
+// The default `register`, `propose` and `request` print to the standard error, the other delegates do nothing
 pub trait ObserverContext<'a> {
     ///the delegate receiving an identifier data from the left part of expression
     fn register<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, ident_type: &'a str) -> T
@@ -41,6 +65,17 @@ pub trait ObserverContext<'a> {
         identifier
     }
 
+    ///the delegate receiving a proposed value under `provenance = true` with where it comes from:
+    ///the sequence number of the proposal, the source text of its expression and the observed identifiers
+    ///the expression mentions, with the sequence numbers of their latest proposals (see [`Provenance`]);
+    ///by default the provenance is dropped and the value proposed, so contexts without a use for it need no override
+    fn propose_sourced<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, _provenance: &Provenance<'a, '_>) -> T
+    where
+        T: Display,
+    {
+        self.propose(identifier, fn_name, ident_name)
+    }
+
     ///the delegate receiving an identifier data from the right part of a statement or function parameters
     fn request<T>(&self, identifier: T, fn_name: &str, ident_name: &str) -> T
     where
@@ -60,4 +95,27 @@ pub trait ObserverContext<'a> {
         eprintln!("{operation}");
         identifier
     }
+
+    ///the delegate receiving a violated `check` invariant
+    ///with the values of the observed variables it mentions
+    fn check_failed(&self, _condition: &'a str, _fn_name: &'a str, _values: &[(&'a str, String)]) {}
+
+    ///the delegate receiving the `Debug` formatted value of a sub-expression
+    ///evaluated under `trace_all = true`, named by its source text
+    fn trace(&self, _expression: &'a str, _fn_name: &'a str, _value: &str) {}
+
+    ///the delegate receiving every statement of the function right before it runs under `step_trace = true`,
+    ///with its line in the source file
+    fn step(&self, _statement: &'a str, _fn_name: &'a str, _line: u32) {}
+
+    ///the delegate receiving the branch taken by an `if` (`then`/`else`) or the arm taken by a `match`
+    ///under `decisions = true`, with the values of the observed variables the condition mentions
+    fn decision(&self, _condition: &'a str, _fn_name: &'a str, _branch: &'a str, _values: &[(&'a str, String)]) {}
+
+    ///the delegate receiving the error a `?` short-circuits with, or the one of a `return Err(..)`,
+    ///under `errors = true`; the error is formatted with `Display`, `Debug` or as `<?>`
+    fn error(&self, _expression: &'a str, _fn_name: &'a str, _error: &str) {}
+
+    ///the delegate receiving the last observed value of a binding going out of scope under `scope_end = true`
+    fn scope_end(&self, _ident_name: &'a str, _fn_name: &'a str, _value: &str) {}
 }
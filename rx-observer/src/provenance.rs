@@ -0,0 +1,26 @@
+//! Sequence numbers of the proposals made under `provenance = true`.
+//!
+//! Every proposal gets a number unique in the process, and its sources refer to the proposals
+//! that produced their values, so a context can follow a value back to the inputs it was computed from.
+use std::sync::atomic::{AtomicU64, Ordering};
+
+///where a proposed value comes from; the names live as long as the context's (`'a`),
+///the list of sources only for the proposal (`'s`)
+#[derive(Copy, Clone, Debug)]
+pub struct Provenance<'a, 's> {
+    ///the number of the proposal, unique in the process
+    pub sequence: u64,
+    ///the source text of the expression the value was computed by
+    pub expression: &'a str,
+    ///the observed identifiers the expression mentions, each with the sequence number of its latest proposal
+    ///in the call, or `None` for inputs, e.g. parameters, registered or requested values
+    pub sources: &'s [(&'a str, Option<u64>)],
+}
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+///the sequence number of the next proposal, starting at 1
+#[doc(hidden)]
+pub fn next_sequence() -> u64 {
+    SEQUENCE.fetch_add(1, Ordering::Relaxed)
+}
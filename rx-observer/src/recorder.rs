@@ -0,0 +1,387 @@
+//! A bounded flight recorder dumped when a decorated function panics.
+//!
+//! Functions decorated with `frame = true` push a [`Frame`] onto a stack of the current thread for the duration
+//! of the call; `async fn`s are rejected, since their calls move between threads. A [`FlightRecorder`] context keeps the latest value of every variable it observes in the frame
+//! of its function and the last `capacity` events of all threads. With [`install_panic_hook`] a panic inside
+//! a decorated function writes the frames of the panicking thread and the recorded events to stderr or a file:
+//! ```text
+//! rx_observer flight recorder: thread 'main' panicked at src/orders.rs:12:5:
+//! index out of bounds: the len is 3 but the index is 7
+//! call stack (innermost last):
+//!   load_orders
+//!     count = 3
+//!   price_order
+//!     index = 7
+//! last 2 events:
+//!   1745932800000000000|main|propose|‹load_orders/count›=3
+//!   1745932800000000001|main|propose|‹price_order/index›=7
+//! ```
+//! The frame of an `async fn` would be pushed on one thread and popped on another, so the attribute rejects it:
+//! ```compile_fail
+//! use rx_observer::prelude::*;
+//! use rx_observer::recorder::FlightRecorder;
+//!
+//! static RECORDER: FlightRecorder = FlightRecorder::new(16);
+//!
+//! #[decorate_vars(context = RECORDER, propose = [total], frame = true)]
+//! async fn quote(price: u32) -> u32 {
+//!     let total = price * 2;
+//!     total
+//! }
+//! # fn main() {}
+//! ```
+use crate::ObserverContext;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::panic::PanicHookInfo;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+///a call of a decorated function on the stack of the current thread,
+///with the latest values recorded for its variables
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub fn_name: &'static str,
+    ///the number of the call, unique across the threads of the process
+    pub invocation: u64,
+    pub values: Vec<(String, String)>,
+}
+
+static NEXT_INVOCATION: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static FRAMES: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+///pops the frame pushed by [`enter`] when dropped, also while unwinding
+pub struct FrameGuard {
+    depth: usize,
+    // the frame is on this thread's stack
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl Drop for FrameGuard {
+    fn drop(&mut self) {
+        FRAMES.with(|frames| frames.borrow_mut().truncate(self.depth));
+    }
+}
+
+///pushes the frame of a call of `fn_name`, emitted by `frame = true`
+#[doc(hidden)]
+pub fn enter(fn_name: &'static str) -> FrameGuard {
+    let depth = FRAMES.with(|frames| {
+        let mut frames = frames.borrow_mut();
+        frames.push(Frame {
+            fn_name,
+            invocation: NEXT_INVOCATION.fetch_add(1, Ordering::Relaxed),
+            values: Vec::new(),
+        });
+        frames.len() - 1
+    });
+    FrameGuard {
+        depth,
+        _not_send: std::marker::PhantomData,
+    }
+}
+
+///the frames of the current thread, outermost first
+pub fn frames() -> Vec<Frame> {
+    FRAMES.with(|frames| frames.try_borrow().map(|frames| frames.clone()).unwrap_or_default())
+}
+
+///the number of the innermost call of `fn_name` on the current thread, `None` without `frame = true`
+pub fn invocation(fn_name: &str) -> Option<u64> {
+    FRAMES
+        .try_with(|frames| {
+            let frames = frames.try_borrow().ok()?;
+            frames
+                .iter()
+                .rev()
+                .find(|frame| frame.fn_name == fn_name)
+                .map(|frame| frame.invocation)
+        })
+        .ok()
+        .flatten()
+}
+
+///records the latest value of the variable in the innermost frame of `fn_name`
+fn record_value(fn_name: &str, ident_name: &str, value: String) {
+    FRAMES.with(|frames| {
+        let Ok(mut frames) = frames.try_borrow_mut() else {
+            return;
+        };
+        let Some(frame) = frames.iter_mut().rev().find(|frame| frame.fn_name == fn_name) else {
+            return;
+        };
+        match frame.values.iter_mut().find(|(name, _)| name == ident_name) {
+            Some((_, latest)) => *latest = value,
+            None => frame.values.push((ident_name.to_string(), value)),
+        }
+    });
+}
+
+///a context keeping the last `capacity` events of all threads
+///and the latest values of the variables in the frames of decorated functions
+pub struct FlightRecorder {
+    capacity: usize,
+    events: Mutex<VecDeque<String>>,
+}
+
+impl FlightRecorder {
+    pub const fn new(capacity: usize) -> Self {
+        FlightRecorder {
+            capacity,
+            events: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn record(&self, operation: &str, fn_name: &str, ident_name: &str, value: &str) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let thread = std::thread::current();
+        let thread = thread.name().unwrap_or("unnamed");
+        let event = format!("{now}|{thread}|{operation}|‹{fn_name}/{ident_name}›={value}");
+
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        if self.capacity > 0 {
+            events.push_back(event);
+        }
+    }
+
+    ///the recorded events, oldest first
+    pub fn events(&self) -> Vec<String> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    ///writes the frames of the current thread and the recorded events
+    pub fn dump(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, "call stack (innermost last):")?;
+        for frame in frames() {
+            writeln!(out, "  {}", frame.fn_name)?;
+            for (name, value) in &frame.values {
+                writeln!(out, "    {name} = {value}")?;
+            }
+        }
+        // a panic while the lock is held must not deadlock the hook
+        let events = match self.events.try_lock() {
+            Ok(events) => events.iter().cloned().collect(),
+            Err(_) => Vec::new(),
+        };
+        writeln!(out, "last {} events:", events.len())?;
+        for event in events {
+            writeln!(out, "  {event}")?;
+        }
+        Ok(())
+    }
+
+    ///clears the recorded events
+    pub fn clear(&self) {
+        self.events.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+impl<'a> ObserverContext<'a> for FlightRecorder {
+    fn register<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, _ident_type: &'a str) -> T
+    where
+        T: Display,
+    {
+        let value = identifier.to_string();
+        self.record("register", fn_name, ident_name, &value);
+        record_value(fn_name, ident_name, value);
+        identifier
+    }
+
+    fn propose<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str) -> T
+    where
+        T: Display,
+    {
+        let value = identifier.to_string();
+        self.record("propose", fn_name, ident_name, &value);
+        record_value(fn_name, ident_name, value);
+        identifier
+    }
+
+    fn request<T>(&self, identifier: T, fn_name: &str, ident_name: &str) -> T
+    where
+        T: Display + FromStr + Clone,
+        <T as FromStr>::Err: Debug,
+    {
+        let value = identifier.to_string();
+        self.record("request", fn_name, ident_name, &value);
+        record_value(fn_name, ident_name, value);
+        identifier
+    }
+
+    fn check_failed(&self, condition: &'a str, fn_name: &'a str, values: &[(&'a str, String)]) {
+        let values = values
+            .iter()
+            .map(|(ident_name, ident_value)| format!("{ident_name}={ident_value}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.record("check failed", fn_name, condition, &values);
+    }
+
+    fn trace(&self, expression: &'a str, fn_name: &'a str, value: &str) {
+        self.record("trace", fn_name, expression, value);
+    }
+
+    fn step(&self, statement: &'a str, fn_name: &'a str, line: u32) {
+        self.record("step", fn_name, &format!("line {line}"), statement);
+    }
+
+    fn decision(&self, condition: &'a str, fn_name: &'a str, branch: &'a str, _values: &[(&'a str, String)]) {
+        self.record("decision", fn_name, condition, branch);
+    }
+
+    fn error(&self, expression: &'a str, fn_name: &'a str, error: &str) {
+        self.record("error", fn_name, expression, error);
+    }
+
+    fn scope_end(&self, ident_name: &'a str, fn_name: &'a str, value: &str) {
+        self.record("scope end", fn_name, ident_name, value);
+    }
+}
+
+///where [`install_panic_hook`] writes the dump
+#[derive(Clone, Debug)]
+pub enum DumpTarget {
+    Stderr,
+    ///appended to the file, which is created when missing
+    File(PathBuf),
+}
+
+///dumps `recorder` when a thread panics inside a decorated function, i.e. with frames on its stack,
+///then runs the previously installed hook
+pub fn install_panic_hook(recorder: &'static FlightRecorder, target: DumpTarget) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info: &PanicHookInfo<'_>| {
+        if !frames().is_empty() {
+            let mut dump = Vec::new();
+            let thread = std::thread::current();
+            let thread = thread.name().unwrap_or("unnamed");
+            let _ = writeln!(dump, "rx_observer flight recorder: thread '{thread}' {info}");
+            let _ = recorder.dump(&mut dump);
+            // the dump is best effort, the panic goes on anyway
+            let _ = match &target {
+                DumpTarget::Stderr => std::io::stderr().write_all(&dump),
+                DumpTarget::File(path) => OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .and_then(|mut file| file.write_all(&dump)),
+            };
+        }
+        previous(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> Vec<&'static str> {
+        frames().iter().map(|frame| frame.fn_name).collect()
+    }
+
+    #[test]
+    fn frames_nest_and_pop_with_their_guards() {
+        let outer = enter("load");
+        {
+            let _inner = enter("price");
+            assert_eq!(names(), ["load", "price"]);
+        }
+        assert_eq!(names(), ["load"]);
+        let _ = std::panic::catch_unwind(|| {
+            let _inner = enter("price");
+            panic!("unwinding pops the frame");
+        });
+        assert_eq!(names(), ["load"]);
+        drop(outer);
+        assert!(frames().is_empty());
+    }
+
+    #[test]
+    fn invocations_are_unique_and_innermost() {
+        assert_eq!(invocation("quote"), None);
+        let first = enter("quote");
+        let outer = invocation("quote").unwrap();
+        let nested = enter("quote");
+        let inner = invocation("quote").unwrap();
+        assert!(inner > outer);
+        let other_thread = std::thread::spawn(|| {
+            let _frame = enter("quote");
+            invocation("quote").unwrap()
+        })
+        .join()
+        .unwrap();
+        assert!(other_thread != outer && other_thread != inner);
+        drop(nested);
+        assert_eq!(invocation("quote"), Some(outer));
+        drop(first);
+        assert_eq!(invocation("quote"), None);
+    }
+
+    #[test]
+    fn values_go_to_the_innermost_frame_of_their_function() {
+        let recorder = FlightRecorder::new(8);
+        let _load = enter("load");
+        recorder.propose(3, "load", "count");
+        let _price = enter("price");
+        recorder.propose(7, "price", "index");
+        recorder.propose(4, "load", "count");
+        recorder.propose(1, "ship", "fee");
+        let values = frames().into_iter().map(|frame| frame.values).collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            [
+                vec![(String::from("count"), String::from("4"))],
+                vec![(String::from("index"), String::from("7"))]
+            ]
+        );
+        assert_eq!(recorder.events().len(), 4);
+    }
+
+    #[test]
+    fn events_are_a_ring_of_capacity() {
+        let recorder = FlightRecorder::new(2);
+        for value in 0..5 {
+            recorder.propose(value, "f", "x");
+        }
+        let events = recorder.events();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].ends_with("|propose|‹f/x›=3") && events[1].ends_with("|propose|‹f/x›=4"));
+
+        let disabled = FlightRecorder::new(0);
+        disabled.propose(1, "f", "x");
+        assert!(disabled.events().is_empty());
+    }
+
+    #[test]
+    fn dump_lists_the_frames_and_the_events() {
+        let recorder = FlightRecorder::new(1);
+        let _frame = enter("price");
+        recorder.propose(7, "price", "index");
+        let mut dump = Vec::new();
+        recorder.dump(&mut dump).unwrap();
+        let dump = String::from_utf8(dump).unwrap();
+        let lines = dump.lines().collect::<Vec<_>>();
+        assert_eq!(lines[..4], ["call stack (innermost last):", "  price", "    index = 7", "last 1 events:"]);
+        assert!(lines[4].ends_with("|propose|‹price/index›=7"));
+    }
+}
@@ -0,0 +1,87 @@
+//! Every observation point compiled into the binary.
+//!
+//! `#[decorate_vars]` submits each static [`Callsite`] it emits, so the points are
+//! known before any decorated function runs, e.g. to check a filter or a formula sheet.
+use crate::callsite::{Callsite, Kind};
+
+#[doc(hidden)]
+pub struct Registration(pub &'static Callsite);
+
+inventory::collect!(Registration);
+
+///all observation points of the binary, ordered by source location
+pub fn observation_points() -> Vec<&'static Callsite> {
+    let mut points = inventory::iter::<Registration>
+        .into_iter()
+        .map(|registration| registration.0)
+        .collect::<Vec<_>>();
+    points.sort_by_key(|callsite| (callsite.file(), callsite.line(), callsite.column()));
+    points
+}
+
+///observation points of the identifier in the given function
+pub fn find(fn_name: &str, ident_name: &str) -> Vec<&'static Callsite> {
+    observation_points()
+        .into_iter()
+        .filter(|callsite| callsite.fn_name() == fn_name && callsite.ident_name() == ident_name)
+        .collect()
+}
+
+///whether the identifier is registered, proposed or requested in any decorated function;
+///traces, steps, decisions and errors name expressions and statements, not observed identifiers
+pub fn is_observable(ident_name: &str) -> bool {
+    inventory::iter::<Registration>.into_iter().any(|registration| {
+        matches!(registration.0.kind(), Kind::Register | Kind::Propose | Kind::Request)
+            && registration.0.ident_name() == ident_name
+    })
+}
+
+///returns the names that are not observed anywhere in the binary,
+///e.g. the variables a formula sheet or a filter refers to by mistake
+pub fn unknown_idents<'n, I>(names: I) -> Vec<&'n str>
+where
+    I: IntoIterator<Item = &'n str>,
+{
+    names
+        .into_iter()
+        .filter(|name| !is_observable(name))
+        .collect()
+}
+
+///observation points a callsite filter (see [`crate::callsite::set_filter`]) would enable
+pub fn matching<F>(filter: F) -> Vec<&'static Callsite>
+where
+    F: Fn(&Callsite) -> bool,
+{
+    observation_points()
+        .into_iter()
+        .filter(|callsite| filter(callsite))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PROPOSED: Callsite = Callsite::new(Kind::Propose, "quote", "total", module_path!(), file!(), line!(), 1);
+    static TRACED: Callsite = Callsite::new(Kind::Trace, "quote", "net", module_path!(), file!(), line!(), 1);
+    static DECIDED: Callsite = Callsite::new(Kind::Decision, "quote", "is_member", module_path!(), file!(), line!(), 1);
+
+    inventory::submit!(Registration(&PROPOSED));
+    inventory::submit!(Registration(&TRACED));
+    inventory::submit!(Registration(&DECIDED));
+
+    #[test]
+    fn only_observed_identifiers_are_observable() {
+        assert!(is_observable("total"));
+        assert!(!is_observable("net"));
+        assert!(!is_observable("is_member"));
+        assert_eq!(unknown_idents(["total", "net"]), ["net"]);
+    }
+
+    #[test]
+    fn every_kind_is_found() {
+        assert_eq!(find("quote", "net").len(), 1);
+        assert!(observation_points().iter().any(|callsite| callsite.kind() == Kind::Decision));
+    }
+}
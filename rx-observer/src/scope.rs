@@ -0,0 +1,408 @@
+//! Ambient contexts installed for a scope of the current thread or tokio task.
+//!
+//! Functions decorated with `context = current` delegate to [`Current`], which routes every observation
+//! to the innermost context installed with [`with_context`], [`set_context`] or, with the `tokio` feature,
+//! `scope_task`. Contexts installed on the thread take precedence over the ones of the task.
+//! Without an installed context observations pass through untouched.
+//!
+//! Ambient contexts receive the values as their `Display` strings (`String`),
+//! and a requested value replaced by the context is parsed back with `FromStr`.
+use crate::{ObserverContext, Provenance};
+use std::cell::{Cell, RefCell};
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+use std::sync::Arc;
+
+///the object-safe face of an `ObserverContext` used by ambient routing,
+///implemented for every context
+pub trait DynObserverContext {
+    fn register_dyn(&self, value: &dyn Display, fn_name: &'static str, ident_name: &'static str, ident_type: &'static str);
+    fn propose_dyn(&self, value: &dyn Display, fn_name: &'static str, ident_name: &'static str);
+    fn propose_sourced_dyn(
+        &self,
+        value: &dyn Display,
+        fn_name: &'static str,
+        ident_name: &'static str,
+        provenance: &Provenance<'static, '_>,
+    );
+    fn request_dyn(&self, value: &dyn Display, fn_name: &str, ident_name: &str) -> String;
+    fn check_failed_dyn(&self, condition: &'static str, fn_name: &'static str, values: &[(&'static str, String)]);
+    fn trace_dyn(&self, expression: &'static str, fn_name: &'static str, value: &str);
+    fn step_dyn(&self, statement: &'static str, fn_name: &'static str, line: u32);
+    fn decision_dyn(&self, condition: &'static str, fn_name: &'static str, branch: &'static str, values: &[(&'static str, String)]);
+    fn error_dyn(&self, expression: &'static str, fn_name: &'static str, error: &str);
+    fn scope_end_dyn(&self, ident_name: &'static str, fn_name: &'static str, value: &str);
+}
+
+impl<C> DynObserverContext for C
+where
+    C: ObserverContext<'static>,
+{
+    fn register_dyn(&self, value: &dyn Display, fn_name: &'static str, ident_name: &'static str, ident_type: &'static str) {
+        self.register(value.to_string(), fn_name, ident_name, ident_type);
+    }
+
+    fn propose_dyn(&self, value: &dyn Display, fn_name: &'static str, ident_name: &'static str) {
+        self.propose(value.to_string(), fn_name, ident_name);
+    }
+
+    fn propose_sourced_dyn(
+        &self,
+        value: &dyn Display,
+        fn_name: &'static str,
+        ident_name: &'static str,
+        provenance: &Provenance<'static, '_>,
+    ) {
+        self.propose_sourced(value.to_string(), fn_name, ident_name, provenance);
+    }
+
+    fn request_dyn(&self, value: &dyn Display, fn_name: &str, ident_name: &str) -> String {
+        self.request(value.to_string(), fn_name, ident_name)
+    }
+
+    fn check_failed_dyn(&self, condition: &'static str, fn_name: &'static str, values: &[(&'static str, String)]) {
+        self.check_failed(condition, fn_name, values);
+    }
+
+    fn trace_dyn(&self, expression: &'static str, fn_name: &'static str, value: &str) {
+        self.trace(expression, fn_name, value);
+    }
+
+    fn step_dyn(&self, statement: &'static str, fn_name: &'static str, line: u32) {
+        self.step(statement, fn_name, line);
+    }
+
+    fn decision_dyn(&self, condition: &'static str, fn_name: &'static str, branch: &'static str, values: &[(&'static str, String)]) {
+        self.decision(condition, fn_name, branch, values);
+    }
+
+    fn error_dyn(&self, expression: &'static str, fn_name: &'static str, error: &str) {
+        self.error(expression, fn_name, error);
+    }
+
+    fn scope_end_dyn(&self, ident_name: &'static str, fn_name: &'static str, value: &str) {
+        self.scope_end(ident_name, fn_name, value);
+    }
+}
+
+#[derive(Clone)]
+enum Installed {
+    /// installed by `with_context` for the duration of its closure, which outlives every use
+    Borrowed(*const (dyn DynObserverContext + 'static)),
+    Shared(Arc<dyn DynObserverContext>),
+}
+
+impl Installed {
+    fn with<R>(&self, f: impl FnOnce(&dyn DynObserverContext) -> R) -> R {
+        match self {
+            // SAFETY: the entry is removed before the borrow it was created from ends
+            Installed::Borrowed(context) => f(unsafe { &**context }),
+            Installed::Shared(context) => f(context.as_ref()),
+        }
+    }
+}
+
+thread_local! {
+    static THREAD_CONTEXTS: RefCell<Vec<(u64, Installed)>> = const { RefCell::new(Vec::new()) };
+    static NEXT_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+#[cfg(feature = "tokio")]
+tokio::task_local! {
+    static TASK_CONTEXTS: Vec<Arc<dyn DynObserverContext + Send + Sync>>;
+}
+
+fn install(context: Installed) -> u64 {
+    let id = NEXT_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        id
+    });
+    THREAD_CONTEXTS.with(|contexts| contexts.borrow_mut().push((id, context)));
+    id
+}
+
+fn uninstall(id: u64) {
+    THREAD_CONTEXTS.with(|contexts| contexts.borrow_mut().retain(|(installed, _)| *installed != id));
+}
+
+///removes the context installed by [`set_context`] from the current thread when dropped
+pub struct ContextGuard {
+    id: u64,
+    // installed on this thread only
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        uninstall(self.id);
+    }
+}
+
+///routes `context = current` observations of the current thread to `context` while `f` runs
+pub fn with_context<C, R>(context: &C, f: impl FnOnce() -> R) -> R
+where
+    C: ObserverContext<'static>,
+{
+    let context: &dyn DynObserverContext = context;
+    // SAFETY: only the lifetime is erased, the guard below uninstalls the pointer before `context` goes out of scope
+    let context: *const (dyn DynObserverContext + 'static) = unsafe { std::mem::transmute(context) };
+    let _guard = ContextGuard {
+        id: install(Installed::Borrowed(context)),
+        _not_send: std::marker::PhantomData,
+    };
+    f()
+}
+
+///routes `context = current` observations of the current thread to `context` until the guard is dropped
+pub fn set_context<C>(context: Arc<C>) -> ContextGuard
+where
+    C: ObserverContext<'static> + 'static,
+{
+    ContextGuard {
+        id: install(Installed::Shared(context)),
+        _not_send: std::marker::PhantomData,
+    }
+}
+
+///routes `context = current` observations of `future` to `context`, wherever the task is polled
+#[cfg(feature = "tokio")]
+pub fn scope_task<C, F>(context: Arc<C>, future: F) -> impl Future<Output = F::Output>
+where
+    C: ObserverContext<'static> + Send + Sync + 'static,
+    F: Future,
+{
+    let mut contexts = TASK_CONTEXTS.try_with(|contexts| contexts.clone()).unwrap_or_default();
+    contexts.push(context);
+    TASK_CONTEXTS.scope(contexts, future)
+}
+
+///the innermost installed context, cloned out so that no borrow is held while it runs
+fn innermost() -> Option<Installed> {
+    let installed = THREAD_CONTEXTS.with(|contexts| contexts.borrow().last().map(|(_, context)| context.clone()));
+    #[cfg(feature = "tokio")]
+    let installed = installed.or_else(|| {
+        TASK_CONTEXTS
+            .try_with(|contexts| contexts.last().cloned())
+            .ok()
+            .flatten()
+            .map(|context| Installed::Shared(context as Arc<dyn DynObserverContext>))
+    });
+    installed
+}
+
+///whether `context = current` observations have a context to go to
+pub fn has_context() -> bool {
+    innermost().is_some()
+}
+
+///the context of `context = current`, delegating to the innermost installed one
+#[derive(Copy, Clone, Default)]
+pub struct Current;
+
+impl ObserverContext<'static> for Current {
+    fn register<T>(&self, identifier: T, fn_name: &'static str, ident_name: &'static str, ident_type: &'static str) -> T
+    where
+        T: Display,
+    {
+        if let Some(context) = innermost() {
+            context.with(|context| context.register_dyn(&identifier, fn_name, ident_name, ident_type));
+        }
+        identifier
+    }
+
+    fn propose<T>(&self, identifier: T, fn_name: &'static str, ident_name: &'static str) -> T
+    where
+        T: Display,
+    {
+        if let Some(context) = innermost() {
+            context.with(|context| context.propose_dyn(&identifier, fn_name, ident_name));
+        }
+        identifier
+    }
+
+    fn propose_sourced<T>(
+        &self,
+        identifier: T,
+        fn_name: &'static str,
+        ident_name: &'static str,
+        provenance: &Provenance<'static, '_>,
+    ) -> T
+    where
+        T: Display,
+    {
+        if let Some(context) = innermost() {
+            context.with(|context| context.propose_sourced_dyn(&identifier, fn_name, ident_name, provenance));
+        }
+        identifier
+    }
+
+    fn request<T>(&self, identifier: T, fn_name: &str, ident_name: &str) -> T
+    where
+        T: Display + FromStr + Clone,
+        <T as FromStr>::Err: Debug,
+    {
+        let Some(context) = innermost() else {
+            return identifier;
+        };
+        let requested = context.with(|context| context.request_dyn(&identifier, fn_name, ident_name));
+        if requested == identifier.to_string() {
+            identifier
+        } else {
+            requested.parse().unwrap_or(identifier)
+        }
+    }
+
+    fn check_failed(&self, condition: &'static str, fn_name: &'static str, values: &[(&'static str, String)]) {
+        if let Some(context) = innermost() {
+            context.with(|context| context.check_failed_dyn(condition, fn_name, values));
+        }
+    }
+
+    fn trace(&self, expression: &'static str, fn_name: &'static str, value: &str) {
+        if let Some(context) = innermost() {
+            context.with(|context| context.trace_dyn(expression, fn_name, value));
+        }
+    }
+
+    fn step(&self, statement: &'static str, fn_name: &'static str, line: u32) {
+        if let Some(context) = innermost() {
+            context.with(|context| context.step_dyn(statement, fn_name, line));
+        }
+    }
+
+    fn decision(&self, condition: &'static str, fn_name: &'static str, branch: &'static str, values: &[(&'static str, String)]) {
+        if let Some(context) = innermost() {
+            context.with(|context| context.decision_dyn(condition, fn_name, branch, values));
+        }
+    }
+
+    fn error(&self, expression: &'static str, fn_name: &'static str, error: &str) {
+        if let Some(context) = innermost() {
+            context.with(|context| context.error_dyn(expression, fn_name, error));
+        }
+    }
+
+    fn scope_end(&self, ident_name: &'static str, fn_name: &'static str, value: &str) {
+        if let Some(context) = innermost() {
+            context.with(|context| context.scope_end_dyn(ident_name, fn_name, value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    ///records the identifiers proposed to it, replaces the requested values by `requested`
+    struct Named {
+        proposed: Mutex<Vec<String>>,
+        requested: &'static str,
+    }
+
+    impl Named {
+        fn new() -> Self {
+            Named {
+                proposed: Mutex::new(Vec::new()),
+                requested: "42",
+            }
+        }
+
+        fn proposed(&self) -> Vec<String> {
+            self.proposed.lock().unwrap().clone()
+        }
+    }
+
+    impl ObserverContext<'static> for Named {
+        fn propose<T>(&self, identifier: T, _fn_name: &'static str, ident_name: &'static str) -> T
+        where
+            T: Display,
+        {
+            self.proposed.lock().unwrap().push(ident_name.to_string());
+            identifier
+        }
+
+        fn request<T>(&self, _identifier: T, _fn_name: &str, _ident_name: &str) -> T
+        where
+            T: Display + FromStr + Clone,
+            <T as FromStr>::Err: Debug,
+        {
+            self.requested.parse().unwrap()
+        }
+    }
+
+    fn propose(ident_name: &'static str) {
+        Current.propose(0, "f", ident_name);
+    }
+
+    #[test]
+    fn nested_scopes_route_to_the_innermost() {
+        let (outer, inner) = (Named::new(), Named::new());
+        assert!(!has_context());
+        with_context(&outer, || {
+            propose("a");
+            with_context(&inner, || propose("b"));
+            propose("c");
+        });
+        assert!(!has_context());
+        propose("d");
+        assert_eq!((outer.proposed(), inner.proposed()), (vec![String::from("a"), String::from("c")], vec![String::from("b")]));
+    }
+
+    #[test]
+    fn a_panicking_scope_uninstalls_its_context() {
+        let context = Named::new();
+        let unwound = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            with_context(&context, || panic!("inside the scope"));
+        }));
+        assert!(unwound.is_err());
+        assert!(!has_context());
+    }
+
+    #[test]
+    fn guards_dropped_out_of_order_uninstall_their_own_context() {
+        let (first, second) = (Arc::new(Named::new()), Arc::new(Named::new()));
+        let first_guard = set_context(first.clone());
+        let second_guard = set_context(second.clone());
+        drop(first_guard);
+        propose("a");
+        drop(second_guard);
+        assert!(!has_context());
+        assert_eq!((first.proposed().len(), second.proposed()), (0, vec![String::from("a")]));
+
+        let first_guard = set_context(first.clone());
+        let second_guard = set_context(second.clone());
+        drop(second_guard);
+        propose("b");
+        drop(first_guard);
+        assert_eq!(first.proposed(), [String::from("b")]);
+    }
+
+    #[test]
+    fn requested_values_are_parsed_back() {
+        let context = Named::new();
+        with_context(&context, || {
+            assert_eq!(Current.request(7u32, "f", "x"), 42);
+            // a replacement not parsing as the type keeps the value
+            assert_eq!(Current.request('x', "f", "x"), 'x');
+        });
+        assert_eq!(Current.request(7u32, "f", "x"), 7);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn thread_contexts_take_precedence_over_the_task_ones() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let (task, nested_task, thread) = (Arc::new(Named::new()), Arc::new(Named::new()), Named::new());
+        runtime.block_on(scope_task(task.clone(), async {
+            propose("a");
+            scope_task(nested_task.clone(), async { propose("b") }).await;
+            with_context(&thread, || propose("c"));
+            propose("d");
+        }));
+        assert!(!has_context());
+        assert_eq!(task.proposed(), [String::from("a"), String::from("d")]);
+        assert_eq!((nested_task.proposed(), thread.proposed()), (vec![String::from("b")], vec![String::from("c")]));
+    }
+}
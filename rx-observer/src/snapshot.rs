@@ -0,0 +1,294 @@
+//! A context keeping the latest value of every observed variable (feature `snapshot`).
+//!
+//! [`SnapshotContext`] stores the registered and proposed values by function and identifier, with the type
+//! of the latest value, and forgets a variable when it goes out of scope under `scope_end = true`,
+//! so the snapshot holds the live variables only. Requests pass the value through unchanged.
+//! Tests read the values back typed:
+//! ```text
+//! quote(3, 4);
+//! assert_eq!(SNAPSHOT.get::<i32>("quote", "total"), Some(12));
+//! ```
+//! Reports are sorted by function and identifier, the keys rendered by the [`KeyFormat`] of the context,
+//! `fn/ident(type)` by default. [`SnapshotContext::write_csv`] and [`SnapshotContext::write_csv_wide`] export
+//! the values for spreadsheets, one row per variable or one column per variable.
+//!
+//! Threads observing the same variable overwrite each other's value, the snapshot holds the one observed last.
+use crate::ObserverContext;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+///how [`SnapshotContext`] renders the keys of its reports
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyFormat {
+    ///`fn/ident(type)`
+    #[default]
+    FnIdentType,
+    ///`fn/ident`
+    FnIdent,
+    ///`ident`, for the snapshots of a single function
+    Ident,
+}
+
+impl KeyFormat {
+    ///the key of a variable of `fn_name`
+    pub fn key(&self, fn_name: &str, ident_name: &str, type_name: &str) -> String {
+        match self {
+            KeyFormat::FnIdentType => format!("{fn_name}/{ident_name}({type_name})"),
+            KeyFormat::FnIdent => format!("{fn_name}/{ident_name}"),
+            KeyFormat::Ident => ident_name.to_string(),
+        }
+    }
+}
+
+///a variable of a decorated function, ordered by function then identifier
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SnapshotKey {
+    pub fn_name: String,
+    pub ident_name: String,
+}
+
+impl SnapshotKey {
+    pub fn new(fn_name: &str, ident_name: &str) -> Self {
+        SnapshotKey {
+            fn_name: fn_name.to_string(),
+            ident_name: ident_name.to_string(),
+        }
+    }
+}
+
+impl Display for SnapshotKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.fn_name, self.ident_name)
+    }
+}
+
+///the latest value of a variable, formatted with `Display`, and its type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotValue {
+    pub type_name: String,
+    pub value: String,
+}
+
+///a context keeping the latest value of every observed variable
+#[derive(Debug, Default)]
+pub struct SnapshotContext {
+    key_format: KeyFormat,
+    vars: RwLock<BTreeMap<SnapshotKey, SnapshotValue>>,
+}
+
+impl SnapshotContext {
+    pub const fn new() -> Self {
+        Self::with_key_format(KeyFormat::FnIdentType)
+    }
+
+    pub const fn with_key_format(key_format: KeyFormat) -> Self {
+        SnapshotContext {
+            key_format,
+            vars: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn key_format(&self) -> KeyFormat {
+        self.key_format
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, BTreeMap<SnapshotKey, SnapshotValue>> {
+        self.vars.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, BTreeMap<SnapshotKey, SnapshotValue>> {
+        self.vars.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    ///the latest value of the variable parsed as `T`, `None` when it is missing or does not parse
+    pub fn get<T: FromStr>(&self, fn_name: &str, ident_name: &str) -> Option<T> {
+        self.read()
+            .get(&SnapshotKey::new(fn_name, ident_name))
+            .and_then(|entry| entry.value.parse().ok())
+    }
+
+    ///the latest value of the variable as formatted with `Display`
+    pub fn get_raw(&self, fn_name: &str, ident_name: &str) -> Option<String> {
+        self.read()
+            .get(&SnapshotKey::new(fn_name, ident_name))
+            .map(|entry| entry.value.clone())
+    }
+
+    ///the type of the latest value of the variable
+    pub fn type_name(&self, fn_name: &str, ident_name: &str) -> Option<String> {
+        self.read()
+            .get(&SnapshotKey::new(fn_name, ident_name))
+            .map(|entry| entry.type_name.clone())
+    }
+
+    ///the variables of `fn_name` with their values, ordered by identifier
+    pub fn iter_fn(&self, fn_name: &str) -> impl Iterator<Item = (String, SnapshotValue)> + use<> {
+        self.read()
+            .range(SnapshotKey::new(fn_name, "")..)
+            .take_while(|(key, _)| key.fn_name == fn_name)
+            .map(|(key, entry)| (key.ident_name.clone(), entry.clone()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    ///a copy of the stored values
+    pub fn values(&self) -> BTreeMap<SnapshotKey, SnapshotValue> {
+        self.read().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    ///`key: value` lines sorted by function and identifier
+    pub fn report_data(&self) -> Vec<String> {
+        self.read()
+            .iter()
+            .map(|(key, entry)| {
+                let key = self.key_format.key(&key.fn_name, &key.ident_name, &entry.type_name);
+                format!("{key}: {}", entry.value)
+            })
+            .collect()
+    }
+
+    ///writes the values as CSV, one row per variable sorted by function and identifier:
+    ///`fn_name,ident_name,type_name,value`
+    pub fn write_csv(&self, mut out: impl Write) -> std::io::Result<()> {
+        let vars = self.values();
+        crate::csv::write_row(&mut out, ["fn_name", "ident_name", "type_name", "value"])?;
+        for (key, entry) in &vars {
+            crate::csv::write_row(
+                &mut out,
+                [&key.fn_name, &key.ident_name, &entry.type_name, &entry.value],
+            )?;
+        }
+        Ok(())
+    }
+
+    ///writes the values as a CSV table of one row, with a column per variable headed by its key
+    pub fn write_csv_wide(&self, mut out: impl Write) -> std::io::Result<()> {
+        let vars = self.values();
+        let keys = vars
+            .iter()
+            .map(|(key, entry)| self.key_format.key(&key.fn_name, &key.ident_name, &entry.type_name));
+        crate::csv::write_row(&mut out, keys)?;
+        crate::csv::write_row(&mut out, vars.values().map(|entry| &entry.value))
+    }
+
+    ///forgets the stored values
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    fn insert(&self, fn_name: &str, ident_name: &str, type_name: &str, value: String) {
+        let entry = SnapshotValue {
+            type_name: type_name.to_string(),
+            value,
+        };
+        self.write().insert(SnapshotKey::new(fn_name, ident_name), entry);
+    }
+}
+
+impl<'a> ObserverContext<'a> for SnapshotContext {
+    fn register<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, ident_type: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.insert(fn_name, ident_name, ident_type, identifier.to_string());
+        identifier
+    }
+
+    fn propose<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str) -> T
+    where
+        T: Display,
+    {
+        let type_name = std::any::type_name_of_val(&identifier);
+        self.insert(fn_name, ident_name, type_name, identifier.to_string());
+        identifier
+    }
+
+    fn request<T>(&self, identifier: T, _fn_name: &str, _ident_name: &str) -> T
+    where
+        T: Display + FromStr + Clone,
+        <T as FromStr>::Err: Debug,
+    {
+        identifier
+    }
+
+    //a snapshot of live variables only
+    fn scope_end(&self, ident_name: &'a str, fn_name: &'a str, _value: &str) {
+        self.write().remove(&SnapshotKey::new(fn_name, ident_name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote_snapshot() -> SnapshotContext {
+        let snapshot = SnapshotContext::new();
+        snapshot.register(120, "quote", "price", "u32");
+        snapshot.propose(0.2f64, "quote", "rate");
+        snapshot.propose(String::from("express"), "quote", "shipping");
+        snapshot.propose(7u8, "quote_batch", "count");
+        snapshot.propose(1i64, "invoice", "total");
+        snapshot
+    }
+
+    #[test]
+    fn get_parses_the_latest_value() {
+        let snapshot = quote_snapshot();
+        assert_eq!(snapshot.get::<u32>("quote", "price"), Some(120));
+        assert_eq!(snapshot.get::<f64>("quote", "rate"), Some(0.2));
+        assert_eq!(snapshot.get::<String>("quote", "shipping").as_deref(), Some("express"));
+        assert_eq!(snapshot.get::<u32>("quote", "shipping"), None);
+        assert_eq!(snapshot.get::<u32>("quote", "missing"), None);
+        snapshot.propose(130, "quote", "price");
+        assert_eq!(snapshot.get::<u32>("quote", "price"), Some(130));
+        assert_eq!(snapshot.type_name("quote", "price").as_deref(), Some("i32"));
+        assert_eq!(snapshot.get_raw("quote", "rate").as_deref(), Some("0.2"));
+    }
+
+    #[test]
+    fn iter_fn_stops_at_the_function() {
+        let snapshot = quote_snapshot();
+        let idents = snapshot.iter_fn("quote").map(|(ident, _)| ident).collect::<Vec<_>>();
+        assert_eq!(idents, ["price", "rate", "shipping"]);
+        let (_, count) = snapshot.iter_fn("quote_batch").next().unwrap();
+        assert_eq!((count.type_name.as_str(), count.value.as_str()), ("u8", "7"));
+        assert_eq!(snapshot.iter_fn("missing").count(), 0);
+    }
+
+    #[test]
+    fn scope_end_forgets_the_variable() {
+        let snapshot = quote_snapshot();
+        snapshot.scope_end("rate", "quote", "0.2");
+        assert_eq!(snapshot.get_raw("quote", "rate"), None);
+        assert_eq!(snapshot.len(), 4);
+    }
+
+    #[test]
+    fn csv_rows_follow_the_keys() {
+        let snapshot = SnapshotContext::with_key_format(KeyFormat::FnIdent);
+        snapshot.propose(String::from("a, \"b\""), "quote", "label");
+        snapshot.propose(3, "quote", "count");
+        let mut long = Vec::new();
+        snapshot.write_csv(&mut long).unwrap();
+        assert_eq!(
+            String::from_utf8(long).unwrap(),
+            "fn_name,ident_name,type_name,value\r\n\
+             quote,count,i32,3\r\n\
+             quote,label,alloc::string::String,\"a, \"\"b\"\"\"\r\n"
+        );
+        let mut wide = Vec::new();
+        snapshot.write_csv_wide(&mut wide).unwrap();
+        assert_eq!(String::from_utf8(wide).unwrap(), "quote/count,quote/label\r\n3,\"a, \"\"b\"\"\"\r\n");
+    }
+}
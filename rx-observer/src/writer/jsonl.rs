@@ -0,0 +1,271 @@
+//! A [`Sink`] streaming the events as JSON Lines with rotation (feature `jsonl`).
+//!
+//! Every event is one JSON object per line, with the fields of a history record:
+//! ```text
+//! {"timestamp":1745932800000000000,"thread":"main","operation":"propose","fn_name":"quote","ident_name":"total","ident_value":"432","type_name":null}
+//! ```
+//! The file at the given path is the active one. Once it would grow past [`Rotation::max_bytes`], or it has been
+//! open for [`Rotation::max_age`], it is renamed to `<stem>.<n>.<extension>`, gzipped to `<stem>.<n>.<extension>.gz`
+//! with [`Rotation::gzip`], and a new active file is started. `n` follows the highest index of the rotated files
+//! in the directory, plain or gzipped, from 1, and is zero-padded to six digits (`events.000012.jsonl`):
+//! sorted by name, the files are in the order of their rotation, for the first 999999 rotations and even once
+//! the older ones are deleted.
+use super::{Event, Sink};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+///when [`JsonLinesSink`] starts a new file, without limits the file is never rotated
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rotation {
+    ///the largest size of a file, a single larger event still gets a file of its own
+    pub max_bytes: Option<u64>,
+    ///the longest time a file is written to
+    pub max_age: Option<Duration>,
+    ///whether the rotated files are gzipped
+    pub gzip: bool,
+}
+
+///writes every event as a JSON object on its own line, rotating the file by [`Rotation`]
+pub struct JsonLinesSink {
+    path: PathBuf,
+    rotation: Rotation,
+    out: BufWriter<File>,
+    ///the size of the active file
+    size: u64,
+    opened: Instant,
+}
+
+fn open(path: &Path) -> std::io::Result<(BufWriter<File>, u64)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok((BufWriter::new(file), size))
+}
+
+impl JsonLinesSink {
+    ///appends to the file at `path`, which is created when missing
+    pub fn new(path: impl Into<PathBuf>, rotation: Rotation) -> std::io::Result<Self> {
+        let path = path.into();
+        let (out, size) = open(&path)?;
+        Ok(JsonLinesSink {
+            path,
+            rotation,
+            out,
+            size,
+            opened: Instant::now(),
+        })
+    }
+
+    fn is_due(&self, line_size: u64) -> bool {
+        let too_big = self
+            .rotation
+            .max_bytes
+            .is_some_and(|max_bytes| self.size > 0 && self.size + line_size > max_bytes);
+        let too_old = self
+            .rotation
+            .max_age
+            .is_some_and(|max_age| self.size > 0 && self.opened.elapsed() >= max_age);
+        too_big || too_old
+    }
+
+    ///`<stem>.<n>.<extension>` with `n` one past the highest index of the rotated files, plain or gzipped,
+    ///padded or not
+    fn rotated_path(&self) -> std::io::Result<PathBuf> {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = self
+            .path
+            .extension()
+            .map(|extension| format!(".{}", extension.to_string_lossy()))
+            .unwrap_or_default();
+        let prefix = format!("{stem}.");
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut last = 0;
+        for entry in std::fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            let index = name
+                .strip_suffix(".gz")
+                .unwrap_or(name)
+                .strip_prefix(&prefix)
+                .and_then(|name| name.strip_suffix(&extension))
+                .filter(|index| index.bytes().all(|byte| byte.is_ascii_digit()))
+                .and_then(|index| index.parse::<u64>().ok());
+            last = last.max(index.unwrap_or_default());
+        }
+        Ok(self.path.with_file_name(format!("{stem}.{:06}{extension}", last + 1)))
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.out.flush()?;
+        let rotated = self.rotated_path()?;
+        std::fs::rename(&self.path, &rotated)?;
+        let (out, size) = open(&self.path)?;
+        self.out = out;
+        self.size = size;
+        self.opened = Instant::now();
+        if self.rotation.gzip {
+            compress(&rotated)?;
+        }
+        Ok(())
+    }
+}
+
+fn gzipped(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+///replaces the file by its gzipped copy
+fn compress(path: &Path) -> std::io::Result<()> {
+    let target = gzipped(path);
+    let mut encoder = GzEncoder::new(File::create(&target)?, Compression::default());
+    std::io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    std::fs::remove_file(path)
+}
+
+impl Sink for JsonLinesSink {
+    fn write(&mut self, event: &Event) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        // the event is written to the active file even when the rotation fails
+        let rotated = if self.is_due(line.len() as u64) { self.rotate() } else { Ok(()) };
+        self.out.write_all(&line)?;
+        self.size += line.len() as u64;
+        rotated
+    }
+
+    //the writer flushes periodically, so an idle file is rotated by age too
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.is_due(0) {
+            self.rotate()
+        } else {
+            self.out.flush()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    ///an empty directory of its own for each test
+    fn directory(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rx_observer_jsonl_{}_{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn event(value: usize) -> Event {
+        Event::new("propose", "quote", "total", value.to_string())
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    ///a rotation for every event
+    const EVERY_EVENT: Rotation = Rotation {
+        max_bytes: Some(1),
+        max_age: None,
+        gzip: false,
+    };
+
+    #[test]
+    fn rotated_files_follow_the_highest_index() {
+        let dir = directory("naming");
+        let mut sink = JsonLinesSink::new(dir.join("events.jsonl"), EVERY_EVENT).unwrap();
+        for value in 0..4 {
+            sink.write(&event(value)).unwrap();
+        }
+        assert_eq!(
+            file_names(&dir),
+            ["events.000001.jsonl", "events.000002.jsonl", "events.000003.jsonl", "events.jsonl"]
+        );
+        // a gap left by a cleanup is not filled, a gzipped or unpadded file counts as well
+        std::fs::remove_file(dir.join("events.000001.jsonl")).unwrap();
+        std::fs::rename(dir.join("events.000003.jsonl"), dir.join("events.3.jsonl.gz")).unwrap();
+        std::fs::write(dir.join("events.x.jsonl"), "").unwrap();
+        sink.write(&event(4)).unwrap();
+        assert_eq!(
+            file_names(&dir),
+            ["events.000002.jsonl", "events.000004.jsonl", "events.3.jsonl.gz", "events.jsonl", "events.x.jsonl"]
+        );
+        let rotated = std::fs::read_to_string(dir.join("events.000004.jsonl")).unwrap();
+        assert!(rotated.contains(r#""ident_value":"3""#), "{rotated}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotated_files_sort_in_rotation_order_past_the_ninth() {
+        let dir = directory("order");
+        let mut sink = JsonLinesSink::new(dir.join("events.jsonl"), EVERY_EVENT).unwrap();
+        for value in 0..12 {
+            sink.write(&event(value)).unwrap();
+        }
+        // the flush rotates the last event too
+        sink.flush().unwrap();
+        let names = file_names(&dir);
+        let (active, rotated) = names.split_last().unwrap();
+        assert_eq!((active.as_str(), rotated.len()), ("events.jsonl", 12));
+        assert_eq!(rotated[9], "events.000010.jsonl");
+        let values = rotated
+            .iter()
+            .map(|name| {
+                let line = std::fs::read_to_string(dir.join(name)).unwrap();
+                serde_json::from_str::<serde_json::Value>(&line).unwrap()["ident_value"].clone()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, (0..12).map(|value| value.to_string()).collect::<Vec<_>>());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn gzipped_files_read_back() {
+        let dir = directory("gzip");
+        let rotation = Rotation {
+            max_bytes: Some(400),
+            gzip: true,
+            ..Rotation::default()
+        };
+        let mut sink = JsonLinesSink::new(dir.join("events.jsonl"), rotation).unwrap();
+        for value in 0..10 {
+            sink.write(&event(value)).unwrap();
+        }
+        sink.flush().unwrap();
+        // the rotated files in order, then the active one
+        let names = file_names(&dir);
+        let (active, rotated) = names.split_last().unwrap();
+        assert_eq!(active, "events.jsonl");
+        assert!(rotated.len() > 1 && rotated.iter().all(|name| name.ends_with(".jsonl.gz")), "{names:?}");
+        let mut text = String::new();
+        for name in rotated {
+            GzDecoder::new(File::open(dir.join(name)).unwrap()).read_to_string(&mut text).unwrap();
+        }
+        text.push_str(&std::fs::read_to_string(dir.join(active)).unwrap());
+        let lines = text.lines().collect::<Vec<_>>();
+        let values = lines
+            .iter()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["ident_value"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(values, (0..10).map(|value| value.to_string()).collect::<Vec<_>>());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,496 @@
+//! A context handing the observations to a background writer thread.
+//!
+//! The default `register`, `propose` and `request` delegates write to stderr on the observing thread. A [`WriterContext`] only
+//! sends an [`Event`] over a bounded channel; a dedicated thread formats it and writes it to a [`Sink`],
+//! a file by default, one line per event:
+//! ```text
+//! 1745932800000000000|main|propose|‹quote/total›=432
+//! 1745932800000000001|worker-1|register|‹quote/price›(u32)=120
+//! ```
+//! The sink is flushed every [`WriterConfig::flush_interval`], on [`WriterContext::flush`] and on shutdown.
+//! When the channel is full the [`Backpressure`] policy decides whether an observing thread waits or the event
+//! is dropped. A static context is never dropped, so call [`WriterContext::shutdown`] before exiting
+//! to write the events still in the channel.
+//!
+//! With the feature `jsonl`, [`jsonl::JsonLinesSink`] writes the events as JSON Lines to size- or time-rotated files.
+#[cfg(feature = "jsonl")]
+pub mod jsonl;
+
+use crate::{ObserverContext, Provenance};
+use std::fmt::{Debug, Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+///an observation on its way to the writer thread
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "jsonl", derive(serde::Serialize))]
+pub struct Event {
+    ///nanoseconds since the Unix epoch
+    pub timestamp: u128,
+    pub thread: String,
+    ///`register`, `propose`, `request`, `check failed`, `trace`, `step`, `decision`, `error` or `scope end`
+    pub operation: &'static str,
+    pub fn_name: String,
+    ///the identifier, or the condition, expression or `line N` of the other operations
+    pub ident_name: String,
+    pub ident_value: String,
+    pub type_name: Option<String>,
+}
+
+impl Event {
+    fn new(operation: &'static str, fn_name: &str, ident_name: &str, ident_value: String) -> Self {
+        let thread = std::thread::current();
+        Event {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
+            thread: thread.name().unwrap_or("unnamed").to_string(),
+            operation,
+            fn_name: fn_name.to_string(),
+            ident_name: ident_name.to_string(),
+            ident_value,
+            type_name: None,
+        }
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}|{}|{}|‹{}/{}›",
+            self.timestamp, self.thread, self.operation, self.fn_name, self.ident_name
+        )?;
+        if let Some(type_name) = &self.type_name {
+            write!(f, "({type_name})")?;
+        }
+        write!(f, "={}", self.ident_value)
+    }
+}
+
+///where the writer thread writes the events
+pub trait Sink: Send + 'static {
+    fn write(&mut self, event: &Event) -> std::io::Result<()>;
+
+    fn flush(&mut self) -> std::io::Result<()>;
+}
+
+///writes every event as a line formatted with `Display`
+pub struct LineSink<W: Write + Send + 'static> {
+    out: BufWriter<W>,
+}
+
+impl<W: Write + Send + 'static> LineSink<W> {
+    pub fn new(out: W) -> Self {
+        LineSink {
+            out: BufWriter::new(out),
+        }
+    }
+}
+
+impl LineSink<File> {
+    ///appends to the file, which is created when missing
+    pub fn append(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(file))
+    }
+}
+
+impl<W: Write + Send + 'static> Sink for LineSink<W> {
+    fn write(&mut self, event: &Event) -> std::io::Result<()> {
+        writeln!(self.out, "{event}")
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+///what an observing thread does when the channel to the writer is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backpressure {
+    ///waits for room, no event is lost
+    Block,
+    ///drops the event
+    Drop,
+    ///waits for room for one of every `n` events finding the channel full and drops the others;
+    ///`0` and `1` behave like `Block`
+    Sample(u32),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct WriterConfig {
+    ///the number of events the channel holds
+    pub capacity: usize,
+    ///the longest time a written event waits in the buffers of the sink
+    pub flush_interval: Duration,
+    pub backpressure: Backpressure,
+}
+
+impl Default for WriterConfig {
+    fn default() -> Self {
+        WriterConfig {
+            capacity: 4096,
+            flush_interval: Duration::from_millis(100),
+            backpressure: Backpressure::Block,
+        }
+    }
+}
+
+enum Message {
+    Event(Event),
+    ///flushes the sink and acknowledges
+    Flush(SyncSender<()>),
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    written: AtomicU64,
+    dropped: AtomicU64,
+    write_errors: AtomicU64,
+    ///the events having found the channel full, counted for `Sample`
+    overflowed: AtomicU64,
+}
+
+///a context writing the observations to a [`Sink`] on a background thread
+pub struct WriterContext {
+    backpressure: Backpressure,
+    ///`None` once shut down
+    sender: RwLock<Option<SyncSender<Message>>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+    counters: Arc<Counters>,
+}
+
+impl WriterContext {
+    ///starts the writer thread
+    pub fn new(sink: impl Sink, config: WriterConfig) -> std::io::Result<Self> {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(config.capacity);
+        let counters = Arc::new(Counters::default());
+        let writer = {
+            let counters = counters.clone();
+            std::thread::Builder::new()
+                .name("rx_observer writer".to_string())
+                .spawn(move || write_events(sink, receiver, config.flush_interval, &counters))?
+        };
+        Ok(WriterContext {
+            backpressure: config.backpressure,
+            sender: RwLock::new(Some(sender)),
+            writer: Mutex::new(Some(writer)),
+            counters,
+        })
+    }
+
+    ///writes the events as lines appended to the file at `path`
+    pub fn to_file(path: impl AsRef<Path>, config: WriterConfig) -> std::io::Result<Self> {
+        Self::new(LineSink::append(path)?, config)
+    }
+
+    fn send(&self, event: Event) {
+        let sender = self.sender.read().unwrap_or_else(|e| e.into_inner());
+        let Some(sender) = sender.as_ref() else {
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        let event = match self.backpressure {
+            Backpressure::Block => Some(Message::Event(event)),
+            Backpressure::Drop | Backpressure::Sample(_) => match sender.try_send(Message::Event(event)) {
+                Ok(()) => None,
+                Err(TrySendError::Disconnected(_)) => {
+                    self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                    None
+                }
+                Err(TrySendError::Full(event)) => {
+                    let overflowed = self.counters.overflowed.fetch_add(1, Ordering::Relaxed) + 1;
+                    match self.backpressure {
+                        Backpressure::Sample(n) if overflowed.is_multiple_of(u64::from(n.max(1))) => Some(event),
+                        _ => {
+                            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                            None
+                        }
+                    }
+                }
+            },
+        };
+        if let Some(event) = event
+            && sender.send(event).is_err()
+        {
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    ///waits until the events sent so far are written and the sink is flushed
+    pub fn flush(&self) {
+        let (ack, acked) = std::sync::mpsc::sync_channel(1);
+        let sent = match self.sender.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            Some(sender) => sender.send(Message::Flush(ack)).is_ok(),
+            None => false,
+        };
+        if sent {
+            let _ = acked.recv();
+        }
+    }
+
+    ///writes the events sent so far, flushes the sink and stops the writer thread;
+    ///the events observed afterwards are dropped
+    pub fn shutdown(&self) {
+        // the writer thread ends when the channel is disconnected
+        self.sender.write().unwrap_or_else(|e| e.into_inner()).take();
+        let writer = self.writer.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(writer) = writer {
+            let _ = writer.join();
+        }
+    }
+
+    ///the number of events written to the sink
+    pub fn written(&self) -> u64 {
+        self.counters.written.load(Ordering::Relaxed)
+    }
+
+    ///the number of events dropped by the backpressure policy or after the shutdown
+    pub fn dropped(&self) -> u64 {
+        self.counters.dropped.load(Ordering::Relaxed)
+    }
+
+    ///the number of failed writes and flushes of the sink
+    pub fn write_errors(&self) -> u64 {
+        self.counters.write_errors.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for WriterContext {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn write_events(mut sink: impl Sink, receiver: Receiver<Message>, flush_interval: Duration, counters: &Counters) {
+    let flush = |sink: &mut dyn Sink| {
+        if sink.flush().is_err() {
+            counters.write_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    };
+    let mut next_flush = Instant::now() + flush_interval;
+    loop {
+        let timeout = next_flush.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(timeout) {
+            Ok(Message::Event(event)) => match sink.write(&event) {
+                Ok(()) => {
+                    counters.written.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(_) => {
+                    counters.write_errors.fetch_add(1, Ordering::Relaxed);
+                }
+            },
+            Ok(Message::Flush(ack)) => {
+                flush(&mut sink);
+                let _ = ack.send(());
+                next_flush = Instant::now() + flush_interval;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                flush(&mut sink);
+                return;
+            }
+        }
+        if Instant::now() >= next_flush {
+            flush(&mut sink);
+            next_flush = Instant::now() + flush_interval;
+        }
+    }
+}
+
+fn joined(values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .map(|(ident_name, ident_value)| format!("{ident_name}={ident_value}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl<'a> ObserverContext<'a> for WriterContext {
+    fn register<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, ident_type: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.send(Event {
+            type_name: Some(ident_type.to_string()),
+            ..Event::new("register", fn_name, ident_name, identifier.to_string())
+        });
+        identifier
+    }
+
+    fn propose<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.send(Event::new("propose", fn_name, ident_name, identifier.to_string()));
+        identifier
+    }
+
+    fn propose_sourced<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, _provenance: &Provenance<'a, '_>) -> T
+    where
+        T: Display,
+    {
+        self.propose(identifier, fn_name, ident_name)
+    }
+
+    fn request<T>(&self, identifier: T, fn_name: &str, ident_name: &str) -> T
+    where
+        T: Display + FromStr + Clone,
+        <T as FromStr>::Err: Debug,
+    {
+        self.send(Event {
+            type_name: Some(std::any::type_name::<T>().to_string()),
+            ..Event::new("request", fn_name, ident_name, identifier.to_string())
+        });
+        identifier
+    }
+
+    fn check_failed(&self, condition: &'a str, fn_name: &'a str, values: &[(&'a str, String)]) {
+        self.send(Event::new("check failed", fn_name, condition, joined(values)));
+    }
+
+    fn trace(&self, expression: &'a str, fn_name: &'a str, value: &str) {
+        self.send(Event::new("trace", fn_name, expression, value.to_string()));
+    }
+
+    fn step(&self, statement: &'a str, fn_name: &'a str, line: u32) {
+        self.send(Event::new("step", fn_name, &format!("line {line}"), statement.to_string()));
+    }
+
+    fn decision(&self, condition: &'a str, fn_name: &'a str, branch: &'a str, values: &[(&'a str, String)]) {
+        self.send(Event::new(
+            "decision",
+            fn_name,
+            condition,
+            format!("{branch} ({})", joined(values)),
+        ));
+    }
+
+    fn error(&self, expression: &'a str, fn_name: &'a str, error: &str) {
+        self.send(Event::new("error", fn_name, expression, error.to_string()));
+    }
+
+    fn scope_end(&self, ident_name: &'a str, fn_name: &'a str, value: &str) {
+        self.send(Event::new("scope end", fn_name, ident_name, value.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::mpsc::sync_channel;
+
+    ///collects the values of the events; the first write can be held until the test opens a gate
+    #[derive(Default)]
+    struct Collecting {
+        values: Arc<Mutex<Vec<String>>>,
+        ///the number of values written when the sink was last flushed
+        flushed: Arc<AtomicUsize>,
+        ///signals the first write, then waits for the gate
+        gate: Option<(SyncSender<()>, Receiver<()>)>,
+    }
+
+    impl Collecting {
+        ///the sink, a receiver of its first write and the sender opening its gate
+        fn gated() -> (Self, Receiver<()>, SyncSender<()>) {
+            let (started, first_write) = sync_channel(1);
+            let (open, gate) = sync_channel(1);
+            let sink = Collecting {
+                gate: Some((started, gate)),
+                ..Collecting::default()
+            };
+            (sink, first_write, open)
+        }
+    }
+
+    impl Sink for Collecting {
+        fn write(&mut self, event: &Event) -> std::io::Result<()> {
+            if let Some((started, gate)) = self.gate.take() {
+                let _ = started.send(());
+                let _ = gate.recv();
+            }
+            self.values.lock().unwrap().push(event.ident_value.clone());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            let written = self.values.lock().unwrap().len();
+            self.flushed.store(written, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    fn config(capacity: usize, backpressure: Backpressure) -> WriterConfig {
+        WriterConfig {
+            capacity,
+            flush_interval: Duration::from_secs(60),
+            backpressure,
+        }
+    }
+
+    #[test]
+    fn drop_counts_the_events_finding_the_channel_full() {
+        let (sink, first_write, open) = Collecting::gated();
+        let values = sink.values.clone();
+        let writer = WriterContext::new(sink, config(1, Backpressure::Drop)).unwrap();
+        writer.propose(0, "f", "x");
+        // the writer holds the first event, the channel has room for one more
+        first_write.recv().unwrap();
+        for value in 1..5 {
+            writer.propose(value, "f", "x");
+        }
+        assert_eq!(writer.dropped(), 3);
+        open.send(()).unwrap();
+        writer.shutdown();
+        assert_eq!(*values.lock().unwrap(), ["0", "1"]);
+        assert_eq!((writer.written(), writer.dropped()), (2, 3));
+    }
+
+    #[test]
+    fn block_delivers_every_event() {
+        let (sink, first_write, open) = Collecting::gated();
+        let values = sink.values.clone();
+        let writer = WriterContext::new(sink, config(1, Backpressure::Block)).unwrap();
+        std::thread::scope(|scope| {
+            let producer = scope.spawn(|| {
+                for value in 0..20 {
+                    writer.propose(value, "f", "x");
+                }
+            });
+            first_write.recv().unwrap();
+            open.send(()).unwrap();
+            producer.join().unwrap();
+        });
+        writer.flush();
+        let expected = (0..20).map(|value| value.to_string()).collect::<Vec<_>>();
+        assert_eq!(*values.lock().unwrap(), expected);
+        assert_eq!((writer.written(), writer.dropped()), (20, 0));
+    }
+
+    #[test]
+    fn shutdown_drains_the_channel_and_flushes() {
+        let (sink, first_write, open) = Collecting::gated();
+        let (values, flushed) = (sink.values.clone(), sink.flushed.clone());
+        let writer = WriterContext::new(sink, config(64, Backpressure::Drop)).unwrap();
+        for value in 0..50 {
+            writer.propose(value, "f", "x");
+        }
+        first_write.recv().unwrap();
+        open.send(()).unwrap();
+        writer.shutdown();
+        assert_eq!(values.lock().unwrap().len(), 50);
+        assert_eq!(flushed.load(Ordering::Relaxed), 50);
+        writer.propose(50, "f", "x");
+        assert_eq!((writer.written(), writer.dropped()), (50, 1));
+    }
+}
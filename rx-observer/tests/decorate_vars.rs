@@ -0,0 +1,837 @@
+//! Expansions of `#[decorate_vars]` compiled and run against a context recording every delegate call.
+use rx_observer::Provenance;
+use rx_observer::prelude::*;
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+///the delegate calls as `operation fn/ident=value`
+#[derive(Default)]
+struct Recording(Mutex<Vec<String>>);
+
+impl Recording {
+    fn record(&self, operation: &str, fn_name: &str, ident_name: &str, value: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("{operation} {fn_name}/{ident_name}={value}"));
+    }
+
+    fn events(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl<'a> ObserverContext<'a> for Recording {
+    fn register<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str, _ident_type: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.record("register", fn_name, ident_name, &identifier.to_string());
+        identifier
+    }
+
+    fn propose<T>(&self, identifier: T, fn_name: &'a str, ident_name: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.record("propose", fn_name, ident_name, &identifier.to_string());
+        identifier
+    }
+
+    fn request<T>(&self, identifier: T, fn_name: &str, ident_name: &str) -> T
+    where
+        T: Display + FromStr + Clone,
+        <T as FromStr>::Err: Debug,
+    {
+        self.record("request", fn_name, ident_name, &identifier.to_string());
+        identifier
+    }
+
+    fn check_failed(&self, condition: &'a str, fn_name: &'a str, _values: &[(&'a str, String)]) {
+        self.record("check failed", fn_name, condition, "");
+    }
+
+    fn trace(&self, expression: &'a str, fn_name: &'a str, value: &str) {
+        self.record("trace", fn_name, expression, value);
+    }
+
+    fn step(&self, statement: &'a str, fn_name: &'a str, line: u32) {
+        self.record("step", fn_name, statement, &line.to_string());
+    }
+
+    fn decision(&self, condition: &'a str, fn_name: &'a str, branch: &'a str, values: &[(&'a str, String)]) {
+        self.record("decision", fn_name, condition, &format!("{branch} {values:?}"));
+    }
+
+    fn error(&self, expression: &'a str, fn_name: &'a str, error: &str) {
+        self.record("error", fn_name, expression, error);
+    }
+
+    fn scope_end(&self, ident_name: &'a str, fn_name: &'a str, value: &str) {
+        self.record("scope end", fn_name, ident_name, value);
+    }
+}
+
+#[decorate_vars(
+    context = param ctx,
+    propose = [b]
+)]
+fn deferred_init(p: i32, q: i32) -> i32 {
+    let b;
+    b = p + q;
+    b
+}
+
+#[test]
+fn proposed_deferred_init_is_assigned_once() {
+    let recording = Recording::default();
+    assert_eq!(deferred_init(&recording, 2, 3), 5);
+    assert_eq!(recording.events(), ["propose deferred_init/b=5"]);
+}
+
+#[decorate_vars(
+    context = param ctx,
+    propose = [total when total > 2]
+)]
+fn conditional_assignment(values: &[i32]) -> i32 {
+    let mut total = 0;
+    for value in values {
+        total = total + value;
+    }
+    total
+}
+
+#[test]
+fn assignment_condition_sees_the_assigned_value() {
+    let recording = Recording::default();
+    assert_eq!(conditional_assignment(&recording, &[1, 2, 3]), 6);
+    assert_eq!(
+        recording.events(),
+        ["propose conditional_assignment/total=3", "propose conditional_assignment/total=6"]
+    );
+}
+
+#[decorate_vars(
+    context = param ctx,
+    propose = [net, fee => audit when fee > 10, total],
+    register = [balance => [audit, ctx]]
+)]
+fn routed_fee(audit: &Recording, amount: u32, mut balance: u32) -> u32 {
+    let net = amount * 9 / 10;
+    let fee = amount - net;
+    let total = net + fee;
+    balance = balance - total;
+    balance
+}
+
+#[test]
+fn routed_entries_reach_only_their_contexts() {
+    let (recording, audit) = (Recording::default(), Recording::default());
+    assert_eq!(routed_fee(&recording, &audit, 50, 100), 50);
+    assert_eq!(
+        recording.events(),
+        [
+            "propose routed_fee/net=45",
+            "propose routed_fee/total=50",
+            "register routed_fee/balance=100",
+            "register routed_fee/balance=50",
+        ]
+    );
+    assert_eq!(audit.events(), ["register routed_fee/balance=100", "register routed_fee/balance=50"]);
+
+    let (recording, audit) = (Recording::default(), Recording::default());
+    routed_fee(&recording, &audit, 200, 300);
+    assert_eq!(
+        audit.events(),
+        ["propose routed_fee/fee=20", "register routed_fee/balance=300", "register routed_fee/balance=100"]
+    );
+    assert_eq!(recording.events().len(), 4);
+}
+
+///a context in a chain, logging into the chain's shared log and appending its name to the requested values
+struct Chained<'l> {
+    name: &'static str,
+    log: &'l Mutex<Vec<String>>,
+}
+
+impl<'a> ObserverContext<'a> for Chained<'_> {
+    fn register<T>(&self, identifier: T, _fn_name: &'a str, ident_name: &'a str, _ident_type: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("{} register {ident_name}={identifier}", self.name));
+        identifier
+    }
+
+    fn propose<T>(&self, identifier: T, _fn_name: &'a str, ident_name: &'a str) -> T
+    where
+        T: Display,
+    {
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("{} propose {ident_name}={identifier}", self.name));
+        identifier
+    }
+
+    fn request<T>(&self, identifier: T, _fn_name: &str, ident_name: &str) -> T
+    where
+        T: Display + FromStr + Clone,
+        <T as FromStr>::Err: Debug,
+    {
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("{} request {ident_name}={identifier}", self.name));
+        format!("{identifier}{}", self.name).parse().unwrap()
+    }
+}
+
+#[decorate_vars(
+    context = [chain.0, chain.1],
+    propose = [label],
+    register = [code],
+    request = [prefix]
+)]
+fn chained_label(chain: &(Chained, Chained), prefix: String) -> String {
+    let code;
+    code = prefix + "-";
+    let label = code.clone();
+    label
+}
+
+#[test]
+fn fanned_out_contexts_are_chained_in_order() {
+    let log = Mutex::new(Vec::new());
+    let chain = (Chained { name: "a", log: &log }, Chained { name: "b", log: &log });
+    assert_eq!(chained_label(&chain, String::from("x")), "xab-");
+    assert_eq!(
+        log.into_inner().unwrap(),
+        [
+            "a request prefix=x",
+            "b request prefix=xa",
+            "a register code=xab-",
+            "b register code=xab-",
+            "a propose label=xab-",
+            "b propose label=xab-",
+        ]
+    );
+}
+
+#[decorate_vars(
+    context = param ctx,
+    propose = [balance],
+    when = id == 7,
+    check = [balance >= 0]
+)]
+fn checked_withdrawal(id: u32, withdrawal: i64) -> i64 {
+    let balance = 50 - withdrawal;
+    balance
+}
+
+#[test]
+fn checks_follow_the_when_condition() {
+    let recording = Recording::default();
+    checked_withdrawal(&recording, 8, 80);
+    assert!(recording.events().is_empty());
+    checked_withdrawal(&recording, 7, 80);
+    assert_eq!(
+        recording.events(),
+        ["propose checked_withdrawal/balance=-30", "check failed checked_withdrawal/balance >= 0="]
+    );
+}
+
+#[decorate_vars(
+    context = param ctx,
+    propose = [fee, net, total],
+    check = [fee < net, net < total]
+)]
+fn ordered_checks(amount: i32) -> i32 {
+    let fee = amount * 2;
+    let net = amount;
+    let total = net - 1;
+    total + fee
+}
+
+#[test]
+fn checks_wait_for_every_variable_they_mention() {
+    let recording = Recording::default();
+    ordered_checks(&recording, 5);
+    assert_eq!(
+        recording.events(),
+        [
+            "propose ordered_checks/fee=10",
+            "propose ordered_checks/net=5",
+            "check failed ordered_checks/fee < net=",
+            "propose ordered_checks/total=4",
+            "check failed ordered_checks/net < total=",
+        ]
+    );
+}
+
+#[decorate_vars(
+    context = param ctx,
+    propose = [stock],
+    check = [stock < 100]
+)]
+fn restocked(stock: u32) -> u32 {
+    let stock = stock * 2;
+    stock
+}
+
+#[test]
+fn checks_are_registered_callsites() {
+    let checks = rx_observer::registry::find("restocked", "stock < 100");
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].kind(), rx_observer::callsite::Kind::Check);
+
+    let recording = Recording::default();
+    checks[0].set_enabled(false);
+    restocked(&recording, 60);
+    checks[0].set_enabled(true);
+    assert_eq!(recording.events(), ["propose restocked/stock=120"]);
+}
+
+struct Till {
+    ctx: Recording,
+    float: u32,
+}
+
+impl Till {
+    #[decorate_vars(
+        context = self.ctx,
+        propose = [total]
+    )]
+    fn ring_up(&self, price: u32) -> u32 {
+        let total = self.float + price;
+        total
+    }
+
+    #[decorate_vars(
+        context = param ctx,
+        propose = [change]
+    )]
+    fn change_for(&self, paid: u32, price: u32) -> u32 {
+        let change = paid - price;
+        change
+    }
+
+    #[decorate_vars(
+        context = param audit: &Recording,
+        propose = [float]
+    )]
+    fn restocked(&mut self, amount: u32) -> u32 {
+        let float = self.float + amount;
+        self.float = float;
+        float
+    }
+}
+
+#[test]
+fn methods_observe_through_their_receivers_and_injected_params() {
+    let mut till = Till {
+        ctx: Recording::default(),
+        float: 10,
+    };
+    assert_eq!(till.ring_up(5), 15);
+    assert_eq!(till.ctx.events(), ["propose ring_up/total=15"]);
+
+    // the injected parameter goes right after the receiver
+    let recording = Recording::default();
+    assert_eq!(till.change_for(&recording, 20, 15), 5);
+    assert_eq!(Till::restocked(&mut till, &recording, 30), 40);
+    assert_eq!(
+        recording.events(),
+        ["propose change_for/change=5", "propose restocked/float=40"]
+    );
+}
+
+#[decorate_vars(
+    context = param ctx: &Recording,
+    propose = [doubled]
+)]
+fn doubled(ctx: &Recording, value: u32) -> u32 {
+    let recorded = ctx.events().len() as u32;
+    let doubled = value * 2 + recorded;
+    doubled
+}
+
+#[test]
+fn existing_params_are_used_as_they_are() {
+    let recording = Recording::default();
+    assert_eq!(doubled(&recording, 4), 8);
+    assert_eq!(doubled(&recording, 4), 9);
+    assert_eq!(recording.events(), ["propose doubled/doubled=8", "propose doubled/doubled=9"]);
+}
+
+#[decorate_vars(
+    context = param _ctx,
+    power_assert = true
+)]
+fn narrowed(a: i32, b: i32, c: u8) {
+    assert!((a + b) as u8 > c);
+}
+
+#[test]
+fn power_assert_shows_the_operands_of_a_cast() {
+    let recording = Recording::default();
+    let panic = std::panic::catch_unwind(|| narrowed(&recording, 200, 100, 50)).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    for line in ["a = 200", "b = 100", "a + b = 300", "(a + b) as u8 = 44", "c = 50"] {
+        assert!(message.lines().any(|captured| captured.trim() == line), "{line} missing in {message}");
+    }
+}
+
+fn counted(calls: &mut u32, value: i32) -> i32 {
+    *calls += 1;
+    value
+}
+
+#[decorate_vars(
+    context = param ctx,
+    register = [limit],
+    propose = [largest],
+    power_assert = true
+)]
+fn capped(prices: &[u32], limit: u32, calls: &mut u32) -> u32 {
+    assert!(counted(calls, 1) > 0);
+    assert_eq!(counted(calls, 2), 2);
+    let largest = prices.iter().copied().max().unwrap_or_default();
+    assert!(prices.iter().all(|price| *price <= limit), "{} prices", prices.len());
+    largest
+}
+
+#[test]
+fn power_assert_evaluates_passing_assertions_once() {
+    let recording = Recording::default();
+    let mut calls = 0;
+    assert_eq!(capped(&recording, &[3, 8], 10, &mut calls), 8);
+    assert_eq!(calls, 2);
+}
+
+#[test]
+fn power_assert_shows_the_observed_variables_mentioned() {
+    let recording = Recording::default();
+    let mut calls = 0;
+    let panic = std::panic::catch_unwind(move || capped(&recording, &[3, 12], 10, &mut calls)).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    let mut lines = message.lines().map(str::trim);
+    let headline = lines.next().unwrap();
+    assert!(headline.starts_with("assertion failed: prices.iter().all(") && headline.ends_with(": 2 prices"));
+    assert_eq!(lines.next_back(), Some("limit = 10"));
+}
+
+#[decorate_vars(
+    context = param ctx,
+    propose = [fee],
+    scope_end = true
+)]
+fn deferred_fee(amount: u32, is_member: bool) -> u32 {
+    let fee;
+    if is_member {
+        fee = amount / 200;
+    } else {
+        fee = amount / 100;
+    }
+    amount + fee
+}
+
+#[test]
+fn deferred_init_binding_reports_its_assigned_value() {
+    let recording = Recording::default();
+    assert_eq!(deferred_fee(&recording, 800, true), 804);
+    assert_eq!(
+        recording.events(),
+        ["propose deferred_fee/fee=4", "scope end deferred_fee/fee=4"]
+    );
+}
+
+#[decorate_vars(
+    context = current,
+    propose = [net, total],
+    provenance = true
+)]
+fn ambient_quote(price: u32, quantity: u32) -> u32 {
+    let net = price * quantity;
+    let total = net + net / 10;
+    total
+}
+
+#[test]
+fn provenance_reaches_ambient_contexts() {
+    let recording = Recording::default();
+    let total = rx_observer::scope::with_context(&recording, || ambient_quote(40, 3));
+    assert_eq!(total, 132);
+    assert_eq!(
+        recording.events(),
+        ["propose ambient_quote/net=120", "propose ambient_quote/total=132"]
+    );
+}
+
+///a proposed identifier with its sequence number and sources
+type SourcedProposal = (String, u64, Vec<(String, Option<u64>)>);
+
+#[derive(Default)]
+struct Sourced(Mutex<Vec<SourcedProposal>>);
+
+impl<'a> ObserverContext<'a> for Sourced {
+    fn propose_sourced<T>(&self, identifier: T, _fn_name: &'a str, ident_name: &'a str, provenance: &Provenance<'a, '_>) -> T
+    where
+        T: Display,
+    {
+        let sources = provenance
+            .sources
+            .iter()
+            .map(|(name, sequence)| (name.to_string(), *sequence))
+            .collect();
+        self.0
+            .lock()
+            .unwrap()
+            .push((ident_name.to_string(), provenance.sequence, sources));
+        identifier
+    }
+}
+
+#[decorate_vars(
+    context = param ctx,
+    propose = [net, tax, total],
+    register = [price, rate],
+    provenance = true
+)]
+fn sourced_quote(price: u32, quantity: u32, rate: u32) -> u32 {
+    let net = price * quantity;
+    let tax = net * rate / 100;
+    let total = net + tax;
+    total
+}
+
+#[test]
+fn proposals_carry_their_sources() {
+    let sourced = Sourced::default();
+    assert_eq!(sourced_quote(&sourced, 120, 3, 20), 432);
+    let proposals = sourced.0.into_inner().unwrap();
+    let [(net, net_seq, net_sources), (tax, tax_seq, tax_sources), (total, _, total_sources)] = &proposals[..] else {
+        panic!("three proposals expected: {proposals:?}");
+    };
+    assert_eq!((net.as_str(), tax.as_str(), total.as_str()), ("net", "tax", "total"));
+    assert_eq!(net_sources, &[(String::from("price"), None)]);
+    assert_eq!(tax_sources, &[(String::from("net"), Some(*net_seq)), (String::from("rate"), None)]);
+    assert_eq!(
+        total_sources,
+        &[(String::from("net"), Some(*net_seq)), (String::from("tax"), Some(*tax_seq))]
+    );
+}
+
+#[decorate_vars(
+    context = param ctx,
+    propose = [net when price > 10, total],
+    provenance = true
+)]
+fn gated_quote(price: u32, quantity: u32) -> u32 {
+    let net = price * quantity;
+    let total = net + 1;
+    total
+}
+
+#[test]
+fn skipped_proposals_are_no_sources() {
+    let sourced = Sourced::default();
+    gated_quote(&sourced, 5, 2);
+    gated_quote(&sourced, 20, 2);
+    let proposals = sourced.0.into_inner().unwrap();
+    let [(skipped_total, _, skipped_sources), (net, net_seq, _), (total, _, total_sources)] = &proposals[..] else {
+        panic!("three proposals expected: {proposals:?}");
+    };
+    assert_eq!((skipped_total.as_str(), net.as_str(), total.as_str()), ("total", "net", "total"));
+    assert_eq!(skipped_sources, &[(String::from("net"), None)]);
+    assert_eq!(total_sources, &[(String::from("net"), Some(*net_seq))]);
+}
+
+fn bump(counter: &mut u32) -> u32 {
+    *counter += 1;
+    *counter
+}
+
+fn restock(shelf: &mut Vec<u32>, item: u32) -> usize {
+    shelf.push(item);
+    shelf.len()
+}
+
+#[decorate_vars(
+    context = param ctx,
+    trace_all = true
+)]
+fn traced_bumps(counter: &mut u32, label: &str) -> String {
+    let bumped = bump(counter) + bump(counter);
+    let mut stock = Vec::new();
+    let shelf = &mut stock;
+    let filled = restock(shelf, bumped) * restock(shelf, 7);
+    let name = label.to_uppercase();
+    let same = describe(&name) == name;
+    format!("{name}{filled}{same}")
+}
+
+fn describe(name: &str) -> String {
+    name.to_string()
+}
+
+#[test]
+fn trace_all_reads_variables_in_place() {
+    let recording = Recording::default();
+    let mut counter = 0;
+    assert_eq!(traced_bumps(&recording, &mut counter, "a"), "A2true");
+    assert_eq!(counter, 2);
+    assert_eq!(
+        recording.events(),
+        [
+            "trace traced_bumps/counter=0",
+            "trace traced_bumps/bump(counter)=1",
+            "trace traced_bumps/counter=1",
+            "trace traced_bumps/bump(counter)=2",
+            "trace traced_bumps/bump(counter) + bump(counter)=3",
+            "trace traced_bumps/Vec :: new()=[]",
+            "trace traced_bumps/shelf=[]",
+            "trace traced_bumps/bumped=3",
+            "trace traced_bumps/restock(shelf, bumped)=1",
+            "trace traced_bumps/shelf=[3]",
+            "trace traced_bumps/restock(shelf, 7)=2",
+            "trace traced_bumps/restock(shelf, bumped) * restock(shelf, 7)=2",
+            "trace traced_bumps/label.to_uppercase()=\"A\"",
+            "trace traced_bumps/& name=\"A\"",
+            "trace traced_bumps/describe(& name)=\"A\"",
+            "trace traced_bumps/name=\"A\"",
+            "trace traced_bumps/describe(& name) == name=true",
+        ]
+    );
+}
+
+#[decorate_vars(
+    context = param ctx,
+    propose = [total],
+    step_trace = true
+)]
+fn stepped_total(prices: &[u32]) -> (u32, u32) {
+    let first = line!();
+    let mut total = 0;
+    for price in prices {
+        if *price > 10 {
+            total += price;
+        }
+    }
+    let halve = |value: u32| {
+        let half = value / 2;
+        half
+    };
+    let total = halve(total);
+    (total, first)
+}
+
+#[test]
+fn steps_report_each_statement_with_its_line() {
+    let recording = Recording::default();
+    let (total, first) = stepped_total(&recording, &[4, 20]);
+    assert_eq!(total, 10);
+    let step = |offset: u32, statement: &str| format!("step stepped_total/{statement}={}", first + offset);
+    assert_eq!(
+        recording.events(),
+        [
+            step(0, "let first = line! ();"),
+            step(1, "let mut total = 0;"),
+            String::from("propose stepped_total/total=0"),
+            step(2, "for price in prices"),
+            step(3, "if * price > 10"),
+            step(3, "if * price > 10"),
+            step(4, "total += price;"),
+            step(7, "let halve = | value : u32 | { let half = value / 2; half };"),
+            step(11, "let total = halve(total);"),
+            step(8, "let half = value / 2;"),
+            step(9, "half"),
+            String::from("propose stepped_total/total=10"),
+            step(12, "(total, first)"),
+        ]
+    );
+}
+
+#[decorate_vars(
+    context = param ctx,
+    register = [amount, code],
+    decisions = true
+)]
+fn shipping(amount: u32, code: &str) -> u32 {
+    let base = if amount > 100 {
+        0
+    } else if amount > 50 {
+        5
+    } else {
+        10
+    };
+    let discount = if let Some(percent) = code.strip_prefix("SAVE") {
+        percent.parse().unwrap_or(0)
+    } else {
+        0
+    };
+    match base {
+        0 => 0,
+        fee if fee > discount => fee - discount,
+        _ => 1,
+    }
+}
+
+#[test]
+fn decisions_report_the_branches_taken() {
+    let recording = Recording::default();
+    assert_eq!(shipping(&recording, 80, "SAVE3"), 2);
+    assert_eq!(shipping(&recording, 20, ""), 10);
+    assert_eq!(shipping(&recording, 120, "SAVE3"), 0);
+    assert_eq!(shipping(&recording, 60, "SAVE9"), 1);
+    let decisions = recording
+        .events()
+        .into_iter()
+        .filter(|event| event.starts_with("decision"))
+        .map(|event| event.trim_start_matches("decision shipping/").to_string())
+        .collect::<Vec<_>>();
+    let code = r#"let Some(percent) = code.strip_prefix("SAVE")"#;
+    assert_eq!(
+        decisions,
+        [
+            String::from(r#"amount > 100=else [("amount", "80")]"#),
+            String::from(r#"amount > 50=then [("amount", "80")]"#),
+            format!(r#"{code}=then [("code", "SAVE3")]"#),
+            String::from("base=fee if fee > discount []"),
+            String::from(r#"amount > 100=else [("amount", "20")]"#),
+            String::from(r#"amount > 50=else [("amount", "20")]"#),
+            format!(r#"{code}=else [("code", "")]"#),
+            String::from("base=fee if fee > discount []"),
+            String::from(r#"amount > 100=then [("amount", "120")]"#),
+            format!(r#"{code}=then [("code", "SAVE3")]"#),
+            String::from("base=0 []"),
+            String::from(r#"amount > 100=else [("amount", "60")]"#),
+            String::from(r#"amount > 50=then [("amount", "60")]"#),
+            format!(r#"{code}=then [("code", "SAVE9")]"#),
+            String::from("base=_ []"),
+        ]
+    );
+}
+
+#[decorate_vars(
+    context = param ctx,
+    register = [level],
+    when = level > 1,
+    decisions = true
+)]
+fn gated_decision(level: u32) -> bool {
+    if level > 2 { true } else { false }
+}
+
+#[test]
+fn decisions_follow_the_when_condition() {
+    let recording = Recording::default();
+    gated_decision(&recording, 1);
+    assert!(recording.events().is_empty());
+    gated_decision(&recording, 3);
+    assert_eq!(
+        recording.events(),
+        ["register gated_decision/level=3", r#"decision gated_decision/level > 2=then [("level", "3")]"#]
+    );
+}
+
+#[derive(Debug)]
+struct Rejected(u32);
+
+struct Opaque;
+
+fn accept_even(value: u32) -> Result<u32, Rejected> {
+    if value.is_multiple_of(2) { Ok(value) } else { Err(Rejected(value)) }
+}
+
+fn accept_small(value: u32) -> Result<u32, Opaque> {
+    if value < 10 { Ok(value) } else { Err(Opaque) }
+}
+
+#[decorate_vars(
+    context = param ctx,
+    errors = true
+)]
+fn parsed(text: &str) -> Result<u32, std::num::ParseIntError> {
+    let value = text.parse::<u32>()?;
+    Ok(value)
+}
+
+#[decorate_vars(
+    context = param ctx,
+    errors = true
+)]
+fn first_even(values: &[u32]) -> Option<u32> {
+    let even = values.iter().find(|value| *value % 2 == 0)?;
+    Some(*even)
+}
+
+#[decorate_vars(
+    context = param ctx,
+    errors = true
+)]
+fn checked(value: u32) -> Result<u32, Rejected> {
+    let even = accept_even(value)?;
+    Ok(even)
+}
+
+#[decorate_vars(
+    context = param ctx,
+    errors = true
+)]
+fn bounded(value: u32) -> Result<u32, Opaque> {
+    let small = accept_small(value)?;
+    Ok(small)
+}
+
+#[decorate_vars(
+    context = param ctx,
+    errors = true
+)]
+fn screened(text: &str) -> Result<u32, String> {
+    let parse = |text: &str| -> Result<u32, std::num::ParseIntError> {
+        let value = text.parse::<u32>()?;
+        Ok(value)
+    };
+    let parsed = async {
+        let value = parse(text).map_err(|error| error.to_string())?;
+        Ok::<u32, String>(value)
+    };
+    let mut parsed = std::pin::pin!(parsed);
+    let waker = std::task::Waker::noop();
+    let std::task::Poll::Ready(parsed) = parsed.as_mut().poll(&mut std::task::Context::from_waker(waker)) else {
+        unreachable!("the block doesn't await");
+    };
+    let value = parsed.unwrap_or(0);
+    if value > 100 {
+        return Err(format!("{value} over 100"));
+    }
+    Ok(value)
+}
+
+#[test]
+fn errors_report_the_short_circuits() {
+    let recording = Recording::default();
+    assert_eq!(parsed(&recording, "42"), Ok(42));
+    assert!(parsed(&recording, "4x2").is_err());
+    assert_eq!(first_even(&recording, &[1, 3]), None);
+    assert_eq!(checked(&recording, 3).unwrap_err().0, 3);
+    assert!(bounded(&recording, 12).is_err());
+    // the `?` of the closure and of the async block short-circuit them, not the function
+    assert_eq!(screened(&recording, "x"), Ok(0));
+    assert_eq!(screened(&recording, "120"), Err(String::from("120 over 100")));
+    assert_eq!(
+        recording.events(),
+        [
+            "error parsed/text.parse :: < u32 > ()=invalid digit found in string",
+            "error first_even/values.iter().find(| value | * value % 2 == 0)=None",
+            "error checked/accept_even(value)=Rejected(3)",
+            "error bounded/accept_small(value)=<?>",
+            "error screened/Err(format! (\"{value} over 100\"))=120 over 100",
+        ]
+    );
+}
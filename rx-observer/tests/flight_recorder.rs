@@ -0,0 +1,56 @@
+//! The panic hook of the flight recorder, in a test binary of its own since the hook is process-wide.
+use rx_observer::prelude::*;
+use rx_observer::recorder::{DumpTarget, FlightRecorder, install_panic_hook};
+
+static RECORDER: FlightRecorder = FlightRecorder::new(2);
+
+#[decorate_vars(
+    context = RECORDER,
+    propose = [count],
+    frame = true
+)]
+fn load_orders(ids: &[usize], index: usize) -> usize {
+    let count = ids.len();
+    price_order(ids, index) + count
+}
+
+#[decorate_vars(
+    context = RECORDER,
+    propose = [position],
+    frame = true
+)]
+fn price_order(ids: &[usize], index: usize) -> usize {
+    let position = index * 2;
+    ids[position]
+}
+
+#[test]
+fn a_panic_in_a_frame_dumps_the_stack_and_the_events() {
+    let path = std::env::temp_dir().join(format!("rx_observer_flight_recorder_{}.txt", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    install_panic_hook(&RECORDER, DumpTarget::File(path.clone()));
+
+    std::panic::catch_unwind(|| panic!("outside of any frame")).unwrap_err();
+    assert!(!path.exists());
+
+    assert_eq!(load_orders(&[10, 20, 30], 1), 33);
+    std::panic::catch_unwind(|| load_orders(&[10, 20, 30], 2)).unwrap_err();
+    let dump = std::fs::read_to_string(&path).unwrap();
+    let lines = dump.lines().collect::<Vec<_>>();
+    assert!(lines[0].starts_with("rx_observer flight recorder: thread '"), "{dump}");
+    assert!(lines[1].starts_with("index out of bounds: the len is 3 but the index is 4"), "{dump}");
+    assert_eq!(
+        lines[2..8],
+        [
+            "call stack (innermost last):",
+            "  load_orders",
+            "    count = 3",
+            "  price_order",
+            "    position = 4",
+            "last 2 events:",
+        ]
+    );
+    assert!(lines[8].ends_with("|propose|‹load_orders/count›=3"), "{dump}");
+    assert!(lines[9].ends_with("|propose|‹price_order/position›=4"), "{dump}");
+    std::fs::remove_file(path).unwrap();
+}